@@ -0,0 +1,39 @@
+use clap::{Args, Subcommand};
+
+use crate::lib::util::size::Size;
+
+#[derive(Args, Clone)]
+pub struct CacheArgs
+{
+    #[command(subcommand)]
+    pub command: CacheCommands,
+
+    #[arg(
+        long = "working-directory",
+        short = 'w',
+        global = true,
+        default_value = ".",
+        help = "Working directory for numake."
+    )]
+    pub workdir: String,
+
+    #[arg(long, short, global = true, help = "Silence numake's output.")]
+    pub quiet: bool
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CacheCommands
+{
+    /// Show the project's extracted dependencies and the shared downloads, least recently used first.
+    List,
+    /// Remove leftovers, then the least recently used entries until the cache fits.
+    Prune {
+        #[arg(long, help = "Size to shrink the cache to, e.g. 5G.")]
+        max_size: Option<Size>,
+    },
+    /// Remove everything cached for a URL.
+    Remove {
+        #[arg(help = "The URL or cache key, as shown by `numake cache list`.")]
+        key: String,
+    },
+}
@@ -0,0 +1,34 @@
+use clap::Args;
+
+#[derive(Args, Clone)]
+pub struct FetchArgs
+{
+    #[arg(
+        help = "Task to run after evaluating the script, for downloads that happen inside tasks."
+    )]
+    pub task: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        help = "The script file to read. By default project.lua, project.luau or numake.lua is searched for, starting in the working directory and going up."
+    )]
+    pub file: Option<String>,
+
+    #[arg(
+        long = "working-directory",
+        short = 'w',
+        default_value = ".",
+        help = "Working directory for numake."
+    )]
+    pub workdir: String,
+
+    #[arg(long, short, help = "Silence numake's output.")]
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        help = "Don't fail on checksum mismatches, print the current sha256 of every download so it can be pinned."
+    )]
+    pub update_hashes: bool
+}
@@ -4,8 +4,12 @@ use clap::Args;
 #[derive(Args, Clone)]
 pub struct ListArgs
 {
-    #[arg(long, short, default_value = "project.lua", help = "The script file to read.")]
-    pub file: String,
+    #[arg(
+        long,
+        short,
+        help = "The script file to read. By default project.lua, project.luau or numake.lua is searched for, starting in the working directory and going up."
+    )]
+    pub file: Option<String>,
 
     #[arg(
         long = "working-directory",
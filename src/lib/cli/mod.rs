@@ -1,15 +1,56 @@
 pub mod sub_commands;
 pub mod numake_args;
 pub mod list_args;
+pub mod fetch_args;
+pub mod update_args;
+pub mod cache_args;
 
 use clap::Parser;
 use crate::lib::cli::sub_commands::SubCommands;
+use crate::lib::ui::message::MessageFormat;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub struct Cli
 {
     #[command(subcommand)]
     pub command: SubCommands,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = MessageFormat::Human,
+        help = "Emit newline-delimited JSON events on stdout instead of the interactive output."
+    )]
+    pub message_format: MessageFormat,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Fail instead of downloading anything that isn't cached yet. file:// URLs still work."
+    )]
+    pub offline: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Don't let the script reach the network at all, whatever it downloads has to be cached already."
+    )]
+    pub deny_network: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Compile the script from source instead of using cached bytecode."
+    )]
+    pub no_script_cache: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Where downloads shared between projects are kept. Defaults to NUMAKE_CACHE_DIR, then the user cache directory (e.g. $XDG_CACHE_HOME/numake)."
+    )]
+    pub cache_dir: Option<String>,
 }
\ No newline at end of file
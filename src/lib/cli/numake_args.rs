@@ -1,4 +1,7 @@
 use clap::Args;
+
+use crate::lib::util::diagnostics::DiagnosticFormat;
+
 #[derive(Args, Clone)]
 pub struct NuMakeArgs
 {
@@ -7,8 +10,12 @@ pub struct NuMakeArgs
     )]
     pub task: String,
 
-    #[arg(long, short, default_value = "project.lua", help = "The script file to read.")]
-    pub file: String,
+    #[arg(
+        long,
+        short,
+        help = "The script file to read. By default project.lua, project.luau or numake.lua is searched for, starting in the working directory and going up."
+    )]
+    pub file: Option<String>,
 
     #[arg(
         long = "working-directory",
@@ -19,5 +26,30 @@ pub struct NuMakeArgs
     pub workdir: String,
 
     #[arg(long, short, help = "Silence numake's output.")]
-    pub quiet: bool
+    pub quiet: bool,
+
+    #[arg(
+        long,
+        short,
+        help = "Keep compiling the remaining files after a failure and report all errors at the end."
+    )]
+    pub keep_going: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = DiagnosticFormat::Human,
+        help = "How to emit the compiler warnings and errors collected during the build."
+    )]
+    pub diagnostic_format: DiagnosticFormat,
+
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        help = "Print the N slowest build steps and write trace.json & timings.html to numake_build."
+    )]
+    pub timings: Option<usize>
 }
\ No newline at end of file
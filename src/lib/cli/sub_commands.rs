@@ -1,7 +1,10 @@
 use clap::Subcommand;
 
+use crate::lib::cli::cache_args::CacheArgs;
+use crate::lib::cli::fetch_args::FetchArgs;
 use crate::lib::cli::list_args::ListArgs;
 use crate::lib::cli::numake_args::NuMakeArgs;
+use crate::lib::cli::update_args::UpdateArgs;
 
 #[derive(Subcommand, Clone)]
 pub enum SubCommands
@@ -10,4 +13,10 @@ pub enum SubCommands
     Build(NuMakeArgs),
     /// List available tasks.
     List(ListArgs),
+    /// Download every dependency of the script and print their hashes.
+    Fetch(FetchArgs),
+    /// Resolve dependencies again and update their entries in numake.lock.
+    Update(UpdateArgs),
+    /// Inspect and clean up cached downloads.
+    Cache(CacheArgs),
 }
\ No newline at end of file
@@ -24,7 +24,10 @@ use crate::lib::{
 		source_file_type::SourceFileType,
 	},
 	runtime::system::System,
-	ui::UI,
+	ui::{
+		message::Message,
+		UI,
+	},
 	util::build_cache::BuildCache,
 };
 use crate::lib::data::flag_type::FlagType;
@@ -114,7 +117,19 @@ impl Generic
 
 		generic_cache = clean_hashes;
 
+		/*
+		 * Failures don't return early so the hashes of everything that did
+		 * compile still end up in the cache below.
+		 */
+		let mut failures: Vec<(PathBuf, anyhow::Error)> = Vec::new();
+
 		for file in source_files.clone() {
+			if System::interrupted()
+				|| (!failures.is_empty() && !self.environment.keep_going)
+			{
+				break;
+			}
+
 			let o_file = obj_dir.join(
 				diff_paths(&file, &self.environment.project_directory)
 					.unwrap()
@@ -126,17 +141,25 @@ impl Generic
 			o_files.push(o_file.to_str().unwrap().to_string());
 
 			if !dirty_files.contains(&&file) {
+				self.ui.emit(Message::CacheHit {
+					key: file.to_str().unwrap_or_default().to_string(),
+				});
 				continue;
 			}
 
+			let _span = self
+				.system
+				.span("compile", file.to_str().unwrap_or_default());
+
 			progress.inc(1);
 			progress.set_message(
 				"Compiling... ".to_string() + file.to_str().unwrap(),
 			);
 			let mut compiler = Command::new(toolset_compiler);
 
-			if !o_file.parent().unwrap().exists() {
-				fs::create_dir_all(o_file.parent().unwrap())?;
+			if let Err(err) = fs::create_dir_all(o_file.parent().unwrap()) {
+				failures.push((file, err.into()));
+				continue;
 			}
 
 			let mut compiler_args = Vec::from([
@@ -163,13 +186,12 @@ impl Generic
 					.args(&compiler_args)
 					.current_dir(&self.environment.project_directory),
 			) {
-				Ok(status) => {
+				Ok(_) => {
 					generic_cache.insert(hashes[&file].clone());
-					Ok(status)
 				}
 
-				Err(err) => Err(err),
-			}?;
+				Err(err) => failures.push((file, err)),
+			}
 		}
 
 		self.ui.remove_bar(progress);
@@ -179,7 +201,7 @@ impl Generic
 			generic_cache,
 		)?;
 
-		Ok(())
+		self.system.collect_failures(failures)
 	}
 
 	fn linking_step(
@@ -191,6 +213,7 @@ impl Generic
 		o_files: Vec<String>,
 	) -> anyhow::Result<()>
 	{
+		let _span = self.system.span("link", project.name.clone());
 		let spinner = self.ui.create_spinner("Linking...");
 		let mut linker = Command::new(toolset_linker);
 		let mut linker_args = Vec::new();
@@ -237,6 +260,11 @@ impl Generic
 
 		self.ui.remove_bar(spinner);
 
+		self.ui.emit(Message::Artifact {
+			project: project.name.clone(),
+			path: out_dir.join(output).to_str().unwrap_or_default().to_string(),
+		});
+
 		Ok(())
 	}
 
@@ -273,7 +301,12 @@ impl Generic
 			o_files,
 		)?;
 
-		project.copy_assets(&self.environment.project_directory, &out_dir)?;
+		let _span = self.system.span("assets", project.name.clone());
+		project.copy_assets(
+			&self.environment.project_directory,
+			&out_dir,
+			&self.environment.path_policy,
+		)?;
 
 		Ok(())
 	}
@@ -27,7 +27,10 @@ use crate::lib::{
 		source_file_type::SourceFileType,
 	},
 	runtime::system::System,
-	ui::UI,
+	ui::{
+		message::Message,
+		UI,
+	},
 	util::build_cache::BuildCache,
 };
 use crate::lib::data::flag_type::FlagType;
@@ -124,8 +127,20 @@ impl MinGW
 
 		mingw_cache = clean_hashes;
 
+		/*
+		 * Failures don't return early so the hashes of everything that did
+		 * compile still end up in the cache below.
+		 */
+		let mut failures: Vec<(PathBuf, anyhow::Error)> = Vec::new();
+
 		// COMPILATION STEP
 		for file in source_files.clone() {
+			if System::interrupted()
+				|| (!failures.is_empty() && !self.environment.keep_going)
+			{
+				break;
+			}
+
 			let o_file = obj_dir.join(
 				diff_paths(&file, &self.environment.project_directory)
 					.unwrap()
@@ -134,16 +149,24 @@ impl MinGW
 					.to_string() + ".o",
 			);
 
-			if !o_file.parent().unwrap().exists() {
-				fs::create_dir_all(o_file.parent().unwrap())?;
+			if let Err(err) = fs::create_dir_all(o_file.parent().unwrap()) {
+				failures.push((file, err.into()));
+				continue;
 			}
 
 			o_files.push(o_file.to_str().unwrap().to_string());
 
 			if !dirty_files.contains(&&file) {
+				self.ui.emit(Message::CacheHit {
+					key: file.to_str().unwrap_or_default().to_string(),
+				});
 				continue;
 			}
 
+			let _span = self
+				.system
+				.span("compile", file.to_str().unwrap_or_default());
+
 			progress.inc(1);
 			progress.set_message(
 				"Compiling... ".to_string() + file.to_str().unwrap(),
@@ -180,20 +203,19 @@ impl MinGW
 					.args(&compiler_args)
 					.current_dir(&self.environment.project_directory),
 			) {
-				Ok(status) => {
+				Ok(_) => {
 					mingw_cache.insert(hashes[&file].clone());
-					Ok(status)
 				}
 
-				Err(err) => Err(err),
-			}?;
+				Err(err) => failures.push((file, err)),
+			}
 		}
 
 		self.ui.remove_bar(progress);
 
 		self.cache.write_set(&cache_name, mingw_cache)?;
 
-		Ok(())
+		self.system.collect_failures(failures)
 	}
 
 	fn resource_step(
@@ -211,6 +233,9 @@ impl MinGW
 			.create_bar(resource_files.len() as u64, "Compiling Resources... ");
 		// RESOURCE FILE HANDLING
 		for resource_file in resource_files {
+			let _span = self
+				.system
+				.span("resource", resource_file.to_str().unwrap_or_default());
 			progress.inc(1);
 			progress.set_message(
 				"Compiling Resources... ".to_string()
@@ -291,6 +316,7 @@ impl MinGW
 			})
 			.collect();
 
+		let _span = self.system.span("link", project.name.clone());
 		let spinner = self.ui.create_spinner("Linking...");
 		match project.project_type {
 			ProjectType::StaticLibrary => {
@@ -369,6 +395,11 @@ impl MinGW
 
 		self.ui.remove_bar(spinner);
 
+		self.ui.emit(Message::Artifact {
+			project: project.name.clone(),
+			path: out_dir.join(output).to_str().unwrap_or_default().to_string(),
+		});
+
 		Ok(())
 	}
 
@@ -417,7 +448,12 @@ impl MinGW
 			o_files,
 		)?;
 
-		project.copy_assets(&self.environment.project_directory, &out_dir)?;
+		let _span = self.system.span("assets", project.name.clone());
+		project.copy_assets(
+			&self.environment.project_directory,
+			&out_dir,
+			&self.environment.path_policy,
+		)?;
 
 		Ok(())
 	}
@@ -33,7 +33,10 @@ use crate::lib::{
 		source_file_type::SourceFileType,
 	},
 	runtime::system::System,
-	ui::UI,
+	ui::{
+		message::Message,
+		UI,
+	},
 	util::{
 		build_cache::BuildCache,
 		download_vswhere,
@@ -42,7 +45,9 @@ use crate::lib::{
 };
 use crate::lib::data::flag_type::FlagType;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
+#[cfg_attr(not(windows), allow(dead_code))]
 pub struct MSVC
 {
 	environment: Environment,
@@ -51,6 +56,7 @@ pub struct MSVC
 	system: System,
 }
 
+#[cfg_attr(not(windows), allow(dead_code))]
 impl MSVC
 {
 	pub fn new(
@@ -218,8 +224,20 @@ impl MSVC
 		 */
 		msvc_cache = clean_hashes;
 
+		/*
+		 * Failures don't return early so the hashes of everything that did
+		 * compile still end up in the cache below.
+		 */
+		let mut failures: Vec<(PathBuf, anyhow::Error)> = Vec::new();
+
 		// COMPILATION STEP
 		for file in source_files.clone() {
+			if System::interrupted()
+				|| (!failures.is_empty() && !self.environment.keep_going)
+			{
+				break;
+			}
+
 			let o_file = obj_dir.join(
 				diff_paths(&file, &self.environment.numake_directory)
 					.unwrap()
@@ -228,16 +246,24 @@ impl MSVC
 					.to_string() + ".obj",
 			);
 
-			if !o_file.parent().unwrap().exists() {
-				fs::create_dir_all(o_file.parent().unwrap())?;
+			if let Err(err) = fs::create_dir_all(o_file.parent().unwrap()) {
+				failures.push((file, err.into()));
+				continue;
 			}
 
 			o_files.push(o_file.to_str().unwrap_or_default().to_string());
 
 			if !dirty_files.contains(&&file) {
+				self.ui.emit(Message::CacheHit {
+					key: file.to_str().unwrap_or_default().to_string(),
+				});
 				continue;
 			}
 
+			let _span = self
+				.system
+				.span("compile", file.to_str().unwrap_or_default());
+
 			progress.inc(1);
 			progress.set_message(
 				"Compiling... ".to_string() + file.to_str().unwrap(),
@@ -269,18 +295,18 @@ impl MSVC
 					.args(&compiler_args)
 					.current_dir(working_directory),
 			) {
-				Ok(status) => {
+				Ok(_) => {
 					msvc_cache.insert(hashes[&file].clone());
-					Ok(status)
 				}
 
-				Err(err) => Err(err),
-			}?;
+				Err(err) => failures.push((file, err)),
+			}
 		}
 		self.ui.remove_bar(progress);
 
 		self.cache.write_set(&cache_name, msvc_cache)?;
-		Ok(())
+
+		self.system.collect_failures(failures)
 	}
 
 	fn resource_step(
@@ -300,6 +326,9 @@ impl MSVC
 			.create_bar(resource_files.len() as u64, "Compiling Resources... ");
 		// RESOURCE FILE HANDLING
 		for resource_file in resource_files {
+			let _span = self
+				.system
+				.span("resource", resource_file.to_str().unwrap_or_default());
 			progress.inc(1);
 			progress.set_message(
 				"Compiling Resources... ".to_string()
@@ -395,6 +424,7 @@ impl MSVC
 		o_files: Vec<String>,
 	) -> anyhow::Result<()>
 	{
+		let _span = self.system.span("link", project.name.clone());
 		let spinner = self.ui.create_spinner("Linking...");
 		// LINKING STEP
 		let mut linker = Command::new(match project.project_type {
@@ -449,6 +479,11 @@ impl MSVC
 
 		self.ui.remove_bar(spinner);
 
+		self.ui.emit(Message::Artifact {
+			project: project.name.clone(),
+			path: out_dir.join(output).to_str().unwrap_or_default().to_string(),
+		});
+
 		Ok(())
 	}
 
@@ -526,7 +561,12 @@ impl MSVC
 			o_files,
 		)?;
 
-		project.copy_assets(&self.environment.project_directory, &out_dir)?;
+		let _span = self.system.span("assets", project.name.clone());
+		project.copy_assets(
+			&self.environment.project_directory,
+			&out_dir,
+			&self.environment.path_policy,
+		)?;
 
 		Ok(())
 	}
@@ -0,0 +1,209 @@
+use crate::lib::util::size::Size;
+use crate::lib::util::store::Codec;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * Settings that belong to the machine or the project rather than to the
+ * script. Read from the file NUMAKE_CONFIG points to, or numake.toml
+ * next to the project script. NUMAKE_CONFIG only stands in for the root
+ * project's, a subproject with a numake.toml of its own still uses that.
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub archive: ArchiveConfig,
+	pub cache: CacheConfig,
+	pub download: DownloadConfig,
+	pub modules: ModulesConfig,
+	pub network: NetworkConfig,
+	pub paths: PathsConfig,
+	pub script: ScriptConfig,
+
+	/*
+	 * URL prefixes to replace before downloading, the longest matching
+	 * one wins:
+	 * [mirrors]
+	 * "https://github.com/" = "file:///mnt/mirror/github/"
+	 */
+	pub mirrors: BTreeMap<String, String>,
+}
+
+/*
+ * [archive]
+ * max_size = "4G"
+ * max_entries = 100000
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+	// Total size of all extracted entries.
+	pub max_size: Size,
+	pub max_entries: usize,
+}
+
+impl Default for ArchiveConfig {
+	fn default() -> Self {
+		ArchiveConfig {
+			max_size: Size(4 << 30),
+			max_entries: 100_000,
+		}
+	}
+}
+
+/*
+ * [cache]
+ * codec = "zstd"          # or "bzip2", "none"; compressed downloads are never recompressed
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+	pub codec: Codec,
+}
+
+/*
+ * [modules]
+ * paths = ["../shared/numake"]   # searched by require() after the project
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModulesConfig {
+	pub paths: Vec<PathBuf>,
+}
+
+/*
+ * [network]
+ * allow = ["github.com", "*.example.com"]   # hosts scripts may download from without asking
+ *
+ * Other hosts are asked about on first use, the answer is remembered
+ * in the project's cache. Without a terminal to ask on they're denied.
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+	pub allow: Vec<String>,
+}
+
+impl NetworkConfig {
+	pub fn allows(
+		&self,
+		host: &str,
+	) -> bool {
+		self.allow.iter().any(|pattern| match pattern.strip_prefix("*.") {
+			Some(domain) => host.ends_with(&format!(".{}", domain)),
+			None => pattern == "*" || pattern == host,
+		})
+	}
+}
+
+/*
+ * [paths]
+ * read = ["/opt/vulkan-sdk", "../third_party"]   # readable by scripts besides the project, numake_build and the download cache
+ */
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+	pub read: Vec<PathBuf>,
+}
+
+/*
+ * [script]
+ * memory_limit = "1G"     # Lua heap, 0 for no limit
+ * time_limit = 60         # seconds of Lua the script and its tasks may run, 0 for no limit
+ *
+ * Waiting for compilers and downloads doesn't count towards the time limit.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptConfig {
+	pub memory_limit: Size,
+	pub time_limit: u64,
+}
+
+impl Default for ScriptConfig {
+	fn default() -> Self {
+		ScriptConfig {
+			memory_limit: Size(1 << 30),
+			time_limit: 60,
+		}
+	}
+}
+
+/*
+ * [download]
+ * retries = 3
+ * backoff = 1.0           # seconds before the first retry, doubled after each
+ * timeout = 30            # seconds without any data before giving up
+ * connect_timeout = 10
+ *
+ * [download.hosts."artifacts.example.com"]
+ * bearer_env = "ARTIFACTS_TOKEN"
+ * headers = { "PRIVATE-TOKEN" = "GITLAB_TOKEN" }
+ *
+ * Credentials are never written in the file itself, only the names of
+ * the environment variables holding them.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadConfig {
+	pub retries: u32,
+	pub backoff: f64,
+	pub timeout: u64,
+	pub connect_timeout: u64,
+	pub hosts: BTreeMap<String, HostConfig>,
+}
+
+impl Default for DownloadConfig {
+	fn default() -> Self {
+		DownloadConfig {
+			retries: 3,
+			backoff: 1.0,
+			timeout: 30,
+			connect_timeout: 10,
+			hosts: BTreeMap::new(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostConfig {
+	// Sent as "Authorization: Bearer <value>".
+	pub bearer_env: Option<String>,
+	// Header name -> environment variable holding its value.
+	pub headers: BTreeMap<String, String>,
+}
+
+impl Config {
+	pub fn mirror(
+		&self,
+		url: &str,
+	) -> String {
+		self.mirrors
+			.iter()
+			.filter(|(from, _)| url.starts_with(from.as_str()))
+			.max_by_key(|(from, _)| from.len())
+			.map(|(from, to)| to.clone() + &url[from.len()..])
+			.unwrap_or_else(|| url.to_string())
+	}
+
+	pub fn load(project_directory: &Path) -> anyhow::Result<Config> {
+		match env::var_os("NUMAKE_CONFIG") {
+			Some(path) => Self::load_file(&PathBuf::from(path)),
+			None => Self::load_file(&project_directory.join("numake.toml")),
+		}
+	}
+
+	pub fn load_file(path: &Path) -> anyhow::Result<Config> {
+		if !path.exists() {
+			return Ok(Config::default());
+		}
+
+		toml::from_str(&fs::read_to_string(path)?).map_err(|err| {
+			anyhow::anyhow!("Invalid config file {}!\n{}", path.display(), err)
+		})
+	}
+}
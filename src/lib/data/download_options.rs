@@ -0,0 +1,94 @@
+use mlua::{FromLua, Lua, Value};
+
+/*
+ * Optional second argument of the network functions, e.g.
+ * network:archive(url, { sha256 = "...", strip_components = 1 })
+ */
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+	// Refers to the dependency in numake.lock and `numake update`.
+	pub name: Option<String>,
+	pub sha256: Option<String>,
+
+	// Only used when extracting archives.
+	pub strip_components: usize,
+	pub subdir: Option<String>,
+
+	// Unified diffs applied after extraction, relative to the project.
+	pub patches: Vec<String>,
+
+	// Only used by network:git. Branch, tag or commit, HEAD by default.
+	pub rev: Option<String>,
+	pub submodules: bool,
+}
+
+impl Default for DownloadOptions {
+	fn default() -> Self {
+		DownloadOptions {
+			name: None,
+			sha256: None,
+			strip_components: 0,
+			subdir: None,
+			patches: Vec::new(),
+			rev: None,
+			submodules: true,
+		}
+	}
+}
+
+impl DownloadOptions {
+	/*
+	 * Extracting the same archive with different options must not end up
+	 * in the same directory. Plain options keep the bare URL as key.
+	 */
+	pub fn extract_key(
+		&self,
+		url: &str,
+	) -> String {
+		match (self.strip_components, &self.subdir) {
+			(0, None) => url.to_string(),
+			(strip, subdir) => format!(
+				"{}#strip={}&subdir={}",
+				url,
+				strip,
+				subdir.clone().unwrap_or_default()
+			),
+		}
+	}
+}
+
+impl FromLua for DownloadOptions {
+	fn from_lua(
+		value: Value,
+		_: &Lua,
+	) -> mlua::Result<Self> {
+		match value {
+			Value::Nil => Ok(DownloadOptions::default()),
+
+			Value::Table(table) => Ok(DownloadOptions {
+				name: table.get::<Option<String>>("name")?,
+				sha256: table
+					.get::<Option<String>>("sha256")?
+					.map(|hash| hash.trim().to_lowercase()),
+				strip_components: table
+					.get::<Option<usize>>("strip_components")?
+					.unwrap_or_default(),
+				subdir: table
+					.get::<Option<String>>("subdir")?
+					.map(|subdir| subdir.trim_matches(['/', '\\']).to_string())
+					.filter(|subdir| !subdir.is_empty()),
+				patches: table
+					.get::<Option<Vec<String>>>("patches")?
+					.unwrap_or_default(),
+				rev: table
+					.get::<Option<String>>("rev")?
+					.map(|rev| rev.trim().to_string()),
+				submodules: table
+					.get::<Option<bool>>("submodules")?
+					.unwrap_or(true),
+			}),
+
+			_ => Err(mlua::Error::UserDataTypeMismatch),
+		}
+	}
+}
@@ -1,8 +1,30 @@
 use std::path::PathBuf;
 
+use crate::lib::data::config::Config;
+use crate::lib::util::diagnostics::DiagnosticFormat;
+use crate::lib::util::path_policy::PathPolicy;
+
 #[derive(Clone, Default)]
 pub struct Environment {
     pub numake_directory: PathBuf,
+    // Downloads shared between projects, see Store.
+    pub cache_directory: PathBuf,
     pub project_directory: PathBuf,
+    // The outermost project's directory, script names are relative to it.
+    pub root_directory: PathBuf,
     pub project_file: PathBuf,
+    pub keep_going: bool,
+    pub diagnostic_format: DiagnosticFormat,
+    pub timings: Option<usize>,
+    pub update_hashes: bool,
+    pub config: Config,
+    // Where scripts may read and write, set up from the root project.
+    pub path_policy: PathPolicy,
+    pub offline: bool,
+    pub deny_network: bool,
+    // `numake list` only needs the tasks, downloads are skipped.
+    pub skip_downloads: bool,
+    pub script_cache: bool,
+    // Set by `numake update`, empty when every dependency is refreshed.
+    pub update: Option<Vec<String>>,
 }
\ No newline at end of file
@@ -14,6 +14,7 @@ use strum_macros::{EnumString, IntoStaticStr};
     Eq,
     PartialEq,
 )]
+#[allow(clippy::upper_case_acronyms)]
 pub enum FlagType {
     #[default]
     Compiler,
@@ -23,9 +24,9 @@ pub enum FlagType {
 }
 
 impl FromLua for FlagType {
-    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
         match value {
-            Value::String(str) => Self::from_str(&str.to_str()?.to_string()).map_err(|e| e.into_lua_err()),
+            Value::String(str) => Self::from_str(&str.to_str()?).map_err(|e| e.into_lua_err()),
             _ => Err(mlua::Error::UserDataTypeMismatch)
         }
     }
@@ -4,4 +4,7 @@ pub mod project;
 pub mod project_type;
 pub mod environment;
 pub mod project_language;
-pub mod flag_type;
\ No newline at end of file
+pub mod flag_type;
+pub mod download_options;pub mod config;
+pub mod pin;
+pub mod walk_options;
@@ -0,0 +1,23 @@
+use std::fmt::{Display, Formatter};
+
+/*
+ * What a downloaded dependency resolved to. Displayed the same way it's
+ * written in the script's options so it can be pasted back in.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pin {
+	Sha256(String),
+	Commit(String),
+}
+
+impl Display for Pin {
+	fn fmt(
+		&self,
+		f: &mut Formatter<'_>,
+	) -> std::fmt::Result {
+		match self {
+			Pin::Sha256(hash) => write!(f, "sha256 = \"{hash}\""),
+			Pin::Commit(commit) => write!(f, "rev = \"{commit}\""),
+		}
+	}
+}
@@ -1,21 +1,18 @@
-use crate::lib::compilers::msvc::MSVC;
 use crate::lib::data::flag_type::FlagType;
 use crate::lib::data::project_language::ProjectLanguage;
 use crate::lib::data::project_type::ProjectType;
 use crate::lib::data::source_file_collection::SourceFileCollection;
 use crate::lib::util::either::Either;
-use crate::lib::util::error::NuMakeError::{
-	AddFileIsDirectory, AssetCopyPathOutsideWorkingDirectory,
-};
+use crate::lib::util::error::NuMakeError::AssetCopyPathOutsideWorkingDirectory;
+use crate::lib::util::path_policy::PathPolicy;
 use anyhow::anyhow;
 use mlua::prelude::LuaValue;
 use mlua::{
-	FromLua, Lua, MetaMethod, UserData, UserDataFields, UserDataMethods, Value,
+	FromLua, Lua, UserData, UserDataMethods, Value,
 };
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 
 #[derive(Debug, Clone, Default)]
 pub struct Project {
@@ -40,28 +37,18 @@ pub struct Project {
 }
 
 impl Project {
-	pub fn add_file(
-		&mut self,
-		file: &PathBuf,
-	) -> anyhow::Result<()> {
-		if file.is_file() {
-			self.source_files.insert(file)
-		} else {
-			return Err(anyhow!(AddFileIsDirectory));
-		}
-		Ok(())
-	}
-
 	pub fn copy_assets(
 		&self,
-		working_directory: &PathBuf,
-		out_dir: &PathBuf,
+		working_directory: &Path,
+		out_dir: &Path,
+		policy: &PathPolicy,
 	) -> anyhow::Result<()> {
+		let out_dir = PathPolicy::resolve(out_dir);
 		for (key, val) in &self.asset_files {
-			let original_path =
-				dunce::canonicalize(working_directory.join(key))?;
-			let copy_path = out_dir.join(val);
-			if !copy_path.starts_with(out_dir) {
+			let original_path = policy
+				.readable(dunce::canonicalize(working_directory.join(key))?)?;
+			let copy_path = policy.writable(out_dir.join(val))?;
+			if !copy_path.starts_with(&out_dir) {
 				Err(anyhow!(AssetCopyPathOutsideWorkingDirectory))?
 			} else {
 				fs::copy(original_path, copy_path)?;
@@ -92,16 +79,16 @@ impl UserData for Project {
 	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
 		methods.add_method_mut(
 			"file",
-			|_, this, value: Either<String, Vec<String>>| {
+			|lua, this, value: Either<String, Vec<String>>| {
 				match value {
 					Either::First(path) => {
-						let path_buf = dunce::canonicalize(path)?;
+						let path_buf = PathPolicy::readable_in(lua, dunce::canonicalize(path)?)?;
 						this.source_files.insert(path_buf);
 					}
 
 					Either::Second(paths) => {
 						for path in paths {
-							let path_buf = dunce::canonicalize(path)?;
+							let path_buf = PathPolicy::readable_in(lua, dunce::canonicalize(path)?)?;
 							this.source_files.insert(path_buf);
 						}
 					}
@@ -118,17 +105,22 @@ impl UserData for Project {
 
 		methods.add_method_mut(
 			"asset",
-			|_, this, (path, out): (String, String)| {
+			|lua, this, (path, out): (String, String)| {
+				PathPolicy::readable_in(lua, &path)?;
 				this.asset_files.insert(path, out);
 				Ok(())
 			},
 		);
     
-		methods.add_method_mut("include", |_, this, value: Either<String, Vec<String>>| {
-			match value {
-				Either::First(path) => this.include_paths.push(path),
-				Either::Second(paths) => this.include_paths.extend(paths),
+		methods.add_method_mut("include", |lua, this, value: Either<String, Vec<String>>| {
+			let paths = match value {
+				Either::First(path) => vec![path],
+				Either::Second(paths) => paths,
+			};
+			for path in &paths {
+				PathPolicy::readable_in(lua, path)?;
 			}
+			this.include_paths.extend(paths);
 			Ok(())
 		});
 
@@ -147,11 +139,15 @@ impl UserData for Project {
 			},
 		);
 
-		methods.add_method_mut("lib_path", |_, this, value: Either<String, Vec<String>>| {
-			match value {
-				Either::First(path) => this.lib_paths.push(path),
-				Either::Second(paths) => this.lib_paths.extend(paths),
+		methods.add_method_mut("lib_path", |lua, this, value: Either<String, Vec<String>>| {
+			let paths = match value {
+				Either::First(path) => vec![path],
+				Either::Second(paths) => paths,
+			};
+			for path in &paths {
+				PathPolicy::readable_in(lua, path)?;
 			}
+			this.lib_paths.extend(paths);
 			Ok(())
 		});
 
@@ -1,7 +1,4 @@
-use crate::lib::data::flag_type::FlagType;
-use mlua::prelude::{LuaResult, LuaValue};
-use mlua::Error::UserDataTypeMismatch;
-use mlua::{ExternalError, FromLua, IntoLua, Lua, Value};
+use mlua::{ExternalError, FromLua, Lua, Value};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use strum_macros::{EnumString, IntoStaticStr};
@@ -9,6 +6,7 @@ use strum_macros::{EnumString, IntoStaticStr};
 #[derive(
 	Debug, Clone, IntoStaticStr, Default, Serialize, Deserialize, EnumString,
 )]
+#[allow(clippy::upper_case_acronyms)]
 pub enum ProjectLanguage {
 	#[default]
 	C,
@@ -18,10 +16,10 @@ pub enum ProjectLanguage {
 impl FromLua for ProjectLanguage {
 	fn from_lua(
 		value: Value,
-		lua: &Lua,
+		_: &Lua,
 	) -> mlua::Result<Self> {
 		match value {
-			Value::String(str) => Self::from_str(&str.to_str()?.to_string())
+			Value::String(str) => Self::from_str(&str.to_str()?)
 				.map_err(|e| e.into_lua_err()),
 			_ => Err(mlua::Error::UserDataTypeMismatch),
 		}
@@ -2,7 +2,6 @@ use crate::lib::data::source_file_type::SourceFileType;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
-use strum::IntoEnumIterator;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SourceFileCollection {
@@ -10,15 +9,6 @@ pub struct SourceFileCollection {
 }
 
 impl SourceFileCollection {
-	pub fn new() -> Self {
-		let mut files: HashMap<SourceFileType, HashSet<PathBuf>> = HashMap::new();
-		for source_file_type in SourceFileType::iter() {
-			files.insert(source_file_type, HashSet::new());
-		}
-
-		Self { files }
-	}
-
 	pub fn insert(
 		&mut self,
 		file: impl Into<PathBuf>,
@@ -46,9 +36,7 @@ impl SourceFileCollection {
 	) -> Vec<PathBuf> {
 		match self.files.get(file_type) {
 			Some(files) => {
-				files.into_iter().map(
-					|file| file.clone()
-				).collect()
+				files.iter().cloned().collect()
 			}
 
 			None => {
@@ -56,8 +44,4 @@ impl SourceFileCollection {
 			}
 		}
 	}
-
-	pub fn clear(&mut self) {
-		self.files.clear();
-	}
 }
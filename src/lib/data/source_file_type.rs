@@ -1,6 +1,4 @@
 use std::path::PathBuf;
-use mlua::{IntoLua, Lua};
-use mlua::prelude::{LuaResult, LuaValue};
 use serde::{Deserialize, Serialize};
 use strum_macros::{EnumIter, IntoStaticStr};
 
@@ -0,0 +1,130 @@
+use crate::lib::util::either::Either;
+use glob::{MatchOptions, Pattern};
+use mlua::{FromLua, Lua, Table, Value};
+use std::path::Path;
+
+/*
+ * Optional third argument of filesystem:walk, e.g.
+ * filesystem:walk("src", true, { glob = { "*.c", "*.h" }, exclude = "tests", hidden = false })
+ *
+ * Patterns match paths relative to the walked directory, with `/` as
+ * separator and `**` for any number of directories. Patterns without a
+ * `/` match the name at any depth, like in .gitignore. A list of extensions like { "c", "cpp" } still works.
+ */
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+	pub extensions: Option<Vec<String>>,
+	pub glob: Vec<Pattern>,
+	// Directories matching one aren't walked at all.
+	pub exclude: Vec<Pattern>,
+	// Files and directories starting with a dot.
+	pub hidden: bool,
+}
+
+impl Default for WalkOptions {
+	fn default() -> Self {
+		WalkOptions {
+			extensions: None,
+			glob: Vec::new(),
+			exclude: Vec::new(),
+			hidden: true,
+		}
+	}
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+	case_sensitive: true,
+	require_literal_separator: true,
+	require_literal_leading_dot: false,
+};
+
+impl WalkOptions {
+	fn matches(
+		patterns: &[Pattern],
+		relative: &Path,
+	) -> bool {
+		let name = relative.file_name().unwrap_or_default().to_string_lossy();
+		let relative = relative.to_string_lossy().replace('\\', "/");
+		patterns.iter().any(|pattern| match pattern.as_str().contains('/') {
+			true => pattern.matches_with(&relative, MATCH_OPTIONS),
+			false => pattern.matches_with(&name, MATCH_OPTIONS),
+		})
+	}
+
+	pub fn include_dir(
+		&self,
+		relative: &Path,
+	) -> bool {
+		(self.hidden || !Self::is_hidden(relative)) && !Self::matches(&self.exclude, relative)
+	}
+
+	pub fn include_file(
+		&self,
+		relative: &Path,
+	) -> bool {
+		let extension = relative
+			.extension()
+			.unwrap_or_default()
+			.to_string_lossy()
+			.to_string();
+
+		(self.hidden || !Self::is_hidden(relative))
+			&& !Self::matches(&self.exclude, relative)
+			&& (self.glob.is_empty() || Self::matches(&self.glob, relative))
+			&& self
+				.extensions
+				.as_ref()
+				.is_none_or(|extensions| extensions.contains(&extension))
+	}
+
+	fn is_hidden(relative: &Path) -> bool {
+		relative
+			.file_name()
+			.is_some_and(|name| name.to_string_lossy().starts_with('.'))
+	}
+
+	fn patterns(
+		table: &Table,
+		key: &str,
+	) -> mlua::Result<Vec<Pattern>> {
+		let patterns = match table.get::<Option<Either<String, Vec<String>>>>(key)? {
+			None => Vec::new(),
+			Some(Either::First(pattern)) => vec![pattern],
+			Some(Either::Second(patterns)) => patterns,
+		};
+
+		patterns
+			.iter()
+			.map(|pattern| {
+				Pattern::new(pattern).map_err(|err| {
+					mlua::Error::runtime(format!("Invalid pattern {}! {}", pattern, err))
+				})
+			})
+			.collect()
+	}
+}
+
+impl FromLua for WalkOptions {
+	fn from_lua(
+		value: Value,
+		lua: &Lua,
+	) -> mlua::Result<Self> {
+		match value {
+			Value::Nil => Ok(WalkOptions::default()),
+
+			Value::Table(table) if table.raw_len() > 0 => Ok(WalkOptions {
+				extensions: Some(Vec::<String>::from_lua(Value::Table(table), lua)?),
+				..Default::default()
+			}),
+
+			Value::Table(table) => Ok(WalkOptions {
+				extensions: table.get::<Option<Vec<String>>>("extensions")?,
+				glob: Self::patterns(&table, "glob")?,
+				exclude: Self::patterns(&table, "exclude")?,
+				hidden: table.get::<Option<bool>>("hidden")?.unwrap_or(true),
+			}),
+
+			_ => Err(mlua::Error::UserDataTypeMismatch),
+		}
+	}
+}
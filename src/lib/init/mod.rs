@@ -1,22 +1,42 @@
+use crate::lib::cli::cache_args::CacheCommands;
 use crate::lib::cli::sub_commands::SubCommands;
 use crate::lib::cli::Cli;
+use crate::lib::data::config::Config;
 use crate::lib::data::environment::Environment;
+use crate::lib::runtime::system::System;
 use crate::lib::runtime::Runtime;
+use crate::lib::ui::message::{Message, MessageFormat};
 use crate::lib::ui::{format, UI};
+use crate::lib::util::cache::Cache;
+use crate::lib::util::diagnostics::DiagnosticFormat;
+use crate::lib::util::error::NuMakeError::{DependencyNotFound, ProjectScriptNotFound};
+use crate::lib::util::path_policy::PathPolicy;
+use crate::lib::util::size::Size;
+use crate::lib::util::store::Store;
+use anyhow::anyhow;
 use clap::Parser;
 use std::env;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Looked for in this order, in every directory from the working directory up.
+const PROJECT_FILES: [&str; 3] = ["project.lua", "project.luau", "numake.lua"];
 
 pub struct Init {}
 
 impl Init {
 	pub fn run() -> ExitCode {
-		let cmd = Self::get_subcommand(&Cli::parse());
-		let ui = Self::init_ui(Self::check_quiet(&cmd));
-		match Self::run_safe(ui.clone(), cmd.clone()) {
+		let cli = Cli::parse();
+		let cmd = Self::get_subcommand(&cli);
+		let ui = Self::init_ui(Self::check_quiet(&cmd), cli.message_format);
+		match Self::run_safe(ui.clone(), cli.clone()) {
 			Ok(_) => { ExitCode::SUCCESS }
 			Err(e) => {
 				ui.println(e.to_string(), format::error::Error::default());
+				ui.emit(Message::Error {
+					message: e.to_string(),
+				});
 				ExitCode::FAILURE
 			}
 		}
@@ -24,24 +44,171 @@ impl Init {
 
 	fn run_safe(
 		ui: UI,
-		cmd: SubCommands,
+		cli: Cli,
 	) -> anyhow::Result<()> {
-		let env = Self::init_environment(&cmd)?;
+		System::handle_interrupts(&ui)?;
+
+		let env = Self::init_environment(&ui, &cli)?;
 		env::set_current_dir(&env.project_directory)?;
 
+		// Doesn't need the script, which may be what's broken.
+		if let SubCommands::Cache(args) = &cli.command {
+			return Self::run_cache(&ui, env, &args.command);
+		}
+
 		let mut runtime = Self::init_runtime(&ui, env.clone())?;
 
 		runtime.execute_script(
 			&env.project_file.to_str().unwrap_or("ERROR").to_string(),
 		)?;
 
-		match cmd {
-			SubCommands::Build(args) => runtime.execute_task(&args.task),
+		match cli.command {
+			SubCommands::Build(args) => {
+				let result = runtime.execute_task(&args.task);
+				runtime.report()?;
+				result
+			}
 
 			SubCommands::List(_) => {
-				println!("Available Tasks: {}", runtime.get_tasks().join(", "));
+				let tasks = runtime.get_tasks();
+				ui.output(
+					format!("Available Tasks: {}", tasks.join(", ")),
+					Message::Tasks { tasks },
+				);
+				Ok(())
+			}
+
+			SubCommands::Fetch(args) => {
+				if let Some(task) = &args.task {
+					runtime.execute_task(task)?;
+				}
+
+				for (url, pin) in runtime.get_fetched() {
+					ui.output(
+						format!("{url}\n  {pin}"),
+						Message::Dependency {
+							pin: pin.to_string(),
+							url,
+						},
+					);
+				}
+				Ok(())
+			}
+
+			SubCommands::Update(args) => {
+				if let Some(task) = &args.task {
+					runtime.execute_task(task)?;
+				}
+
+				let lockfile = runtime.get_lockfile();
+				if args.names.is_empty() {
+					lockfile.prune_unseen()?;
+				}
+
+				for name in &args.names {
+					if !lockfile.was_seen(name) {
+						return Err(anyhow!(DependencyNotFound(name.clone())));
+					}
+				}
+
+				ui.println("numake.lock is up to date.", format::ok::Ok::default());
 				Ok(())
 			}
+
+			SubCommands::Cache(_) => unreachable!("handled before the script runs"),
+		}
+	}
+
+	fn run_cache(
+		ui: &UI,
+		env: Environment,
+		command: &CacheCommands,
+	) -> anyhow::Result<()> {
+		let mut cache = Cache::new(env)?;
+
+		match command {
+			CacheCommands::List => {
+				let entries = cache.entries()?;
+				let total: u64 = entries.iter().map(|entry| entry.size).sum();
+
+				for entry in &entries {
+					ui.output(
+						format!(
+							"{:>10}  {:>9}  {}{}",
+							Size(entry.size).to_string(),
+							Self::format_age(entry.last_used),
+							if entry.is_shared() { "[shared] " } else { "" },
+							entry.key
+						),
+						Message::CacheEntry {
+							key: entry.key.clone(),
+							size: entry.size,
+							last_used: entry.last_used,
+							shared: entry.is_shared(),
+						},
+					);
+				}
+				ui.output(
+					format!(
+						"{} in {} entries, shared downloads are kept in {}",
+						Size(total),
+						entries.len(),
+						cache.get_store_directory().display()
+					),
+					Message::CacheSummary {
+						size: total,
+						entries: entries.len(),
+						store: cache.get_store_directory().display().to_string(),
+					},
+				);
+				Ok(())
+			}
+
+			CacheCommands::Prune { max_size } => {
+				let removed = cache.prune(*max_size)?;
+				for entry in &removed {
+					ui.println(
+						format!("Removed {} ({})", entry.key, Size(entry.size)),
+						format::info::Info::default(),
+					);
+				}
+
+				let freed: u64 = removed.iter().map(|entry| entry.size).sum();
+				ui.println(
+					format!("Freed {} from {} entries.", Size(freed), removed.len()),
+					format::ok::Ok::default(),
+				);
+				Ok(())
+			}
+
+			CacheCommands::Remove { key } => {
+				let removed = cache.remove(key)?;
+				if removed.is_empty() {
+					return Err(anyhow!("Nothing is cached for {}!", key));
+				}
+
+				for entry in &removed {
+					ui.println(
+						format!("Removed {} ({})", entry.key, Size(entry.size)),
+						format::ok::Ok::default(),
+					);
+				}
+				Ok(())
+			}
+		}
+	}
+
+	fn format_age(timestamp: u64) -> String {
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|time| time.as_secs())
+			.unwrap_or(0);
+
+		match now.saturating_sub(timestamp) {
+			age if age < 60 => "just now".to_string(),
+			age if age < 60 * 60 => format!("{}m ago", age / 60),
+			age if age < 24 * 60 * 60 => format!("{}h ago", age / (60 * 60)),
+			age => format!("{}d ago", age / (24 * 60 * 60)),
 		}
 	}
 
@@ -52,38 +219,129 @@ impl Init {
 		Runtime::new(ui.clone(), env)
 	}
 
-	fn init_ui(quiet: bool) -> UI {
-		UI::new(quiet)
+	fn init_ui(
+		quiet: bool,
+		message_format: MessageFormat,
+	) -> UI {
+		UI::new(quiet, message_format)
 	}
 
 	fn get_subcommand(args: &Cli) -> SubCommands {
 		args.command.clone()
 	}
 
-	fn init_environment(command: &SubCommands) -> anyhow::Result<Environment> {
-		let mut project_dir_str = "";
-		let mut project_file_str = "";
+	/*
+	 * The first directory from `start` up that has a project script, like
+	 * git looks for .git.
+	 */
+	fn find_project_file(start: &Path) -> Option<PathBuf> {
+		start.ancestors().find_map(|directory| {
+			PROJECT_FILES
+				.iter()
+				.map(|file| directory.join(file))
+				.find(|path| path.is_file())
+		})
+	}
 
-		match command {
+	fn init_environment(
+		ui: &UI,
+		cli: &Cli,
+	) -> anyhow::Result<Environment> {
+		let command = &cli.command;
+		let (project_dir_str, project_file_str) = match command {
+			SubCommands::Build(args) => (args.workdir.as_str(), args.file.as_deref()),
+
+			SubCommands::List(args) => (args.workdir.as_str(), args.file.as_deref()),
+
+			SubCommands::Fetch(args) => (args.workdir.as_str(), args.file.as_deref()),
+
+			SubCommands::Update(args) => (args.workdir.as_str(), args.file.as_deref()),
+
+			SubCommands::Cache(args) => (args.workdir.as_str(), None),
+		};
+
+		let (keep_going, diagnostic_format, timings) = match command {
 			SubCommands::Build(args) => {
-				project_dir_str = args.workdir.as_str();
-				project_file_str = args.file.as_str()
+				(args.keep_going, args.diagnostic_format, args.timings)
 			}
 
-			SubCommands::List(args) => {
-				project_dir_str = args.workdir.as_str();
-				project_file_str = args.file.as_str()
-			}
-		}
+			_ => (false, DiagnosticFormat::default(), None),
+		};
+
+		let update_hashes = match command {
+			SubCommands::Fetch(args) => args.update_hashes,
+
+			_ => false,
+		};
+
+		let update = match command {
+			SubCommands::Update(args) => Some(args.names.clone()),
 
-		let project_directory = dunce::canonicalize(project_dir_str)?;
-		let project_file = project_directory.join(project_file_str);
+			_ => None,
+		};
+
+		let working_directory = dunce::canonicalize(project_dir_str)?;
+		let (project_directory, project_file) = match project_file_str {
+			Some(file) => (working_directory.clone(), working_directory.join(file)),
+
+			None => match Self::find_project_file(&working_directory) {
+				Some(file) => {
+					ui.println(
+						format!("Using {}", file.display()),
+						format::info::Info::default(),
+					);
+					(file.parent().unwrap_or(&working_directory).to_path_buf(), file)
+				}
+
+				// The cache can be managed without a project.
+				None if matches!(command, SubCommands::Cache(_)) => {
+					(working_directory.clone(), working_directory.join(PROJECT_FILES[0]))
+				}
+
+				None => {
+					return Err(anyhow!(ProjectScriptNotFound(
+						working_directory.display().to_string()
+					)))
+				}
+			},
+		};
 		let numake_directory = project_directory.join("numake_build");
+		let cache_directory = match cli
+			.cache_dir
+			.as_ref()
+			.map(PathBuf::from)
+			.or_else(Store::default_directory)
+		{
+			Some(path) => std::path::absolute(path)?,
+			// Without a home directory downloads stay with the project, apart
+			// from its .cache so cache prune and remove leave the store alone.
+			None => numake_directory.join(".store"),
+		};
+		let config = Config::load(&project_directory)?;
+		let path_policy = PathPolicy::new(
+			&project_directory,
+			&numake_directory,
+			&cache_directory,
+			&config,
+		);
 
 		Ok(Environment {
 			project_file,
+			root_directory: project_directory.clone(),
 			project_directory,
 			numake_directory,
+			cache_directory,
+			keep_going,
+			diagnostic_format,
+			timings,
+			update_hashes,
+			config,
+			path_policy,
+			offline: cli.offline,
+			deny_network: cli.deny_network,
+			skip_downloads: matches!(command, SubCommands::List(_)),
+			script_cache: !cli.no_script_cache,
+			update,
 		})
 	}
 
@@ -92,6 +350,12 @@ impl Init {
 			SubCommands::Build(args) => args.quiet,
 
 			SubCommands::List(args) => args.quiet,
+
+			SubCommands::Fetch(args) => args.quiet,
+
+			SubCommands::Update(args) => args.quiet,
+
+			SubCommands::Cache(args) => args.quiet,
 		}
 	}
 }
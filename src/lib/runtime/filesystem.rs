@@ -1,8 +1,22 @@
 use crate::lib::data::environment::Environment;
+use crate::lib::data::walk_options::WalkOptions;
+use crate::lib::util::into_lua_error;
+use crate::lib::util::path_policy::PathPolicy;
+use anyhow::Context;
 use mlua::{UserData, UserDataMethods};
+use pathdiff::diff_paths;
 use std::fs;
 use std::path::PathBuf;
 
+/*
+ * The `filesystem` global. Paths scripts read from are relative to the
+ * project, paths they write to relative to numake_build/gen, both go
+ * through the project's path policy:
+ *
+ *   local version = filesystem:read("VERSION")
+ *   local header = filesystem:write("version.h", "#define VERSION \"" .. version .. "\"\n")
+ *   project:include(filesystem:relative(filesystem:mkdir("include")))
+ */
 #[derive(Clone)]
 pub struct Filesystem {
 	environment: Environment,
@@ -13,54 +27,122 @@ impl Filesystem {
 		Filesystem { environment }
 	}
 
+	fn readable(
+		&self,
+		path: &str,
+	) -> anyhow::Result<PathBuf> {
+		self.environment
+			.path_policy
+			.readable(self.environment.project_directory.join(path))
+	}
+
+	fn writable(
+		&self,
+		path: &str,
+	) -> anyhow::Result<PathBuf> {
+		self.environment
+			.path_policy
+			.writable(self.environment.numake_directory.join("gen").join(path))
+	}
+
 	pub fn walk_dir(
 		&self,
 		path_buf: PathBuf,
+		relative: PathBuf,
 		recursive: bool,
-		file_filter: Option<Vec<String>>,
+		options: &WalkOptions,
 	) -> anyhow::Result<Vec<PathBuf>> {
 		let mut path_vec: Vec<PathBuf> = Vec::new();
 
 		for entry in fs::read_dir(path_buf)? {
-			let path = dunce::canonicalize(entry?.path())?;
-			if path.is_dir() && recursive {
-				path_vec.append(&mut self.walk_dir(path.clone(), true, file_filter.clone())?.clone())
+			let entry = entry?;
+			let relative = relative.join(entry.file_name());
+			let path = dunce::canonicalize(entry.path())?;
+			// A symlink inside the project may still point outside of it.
+			if path.is_file() || recursive {
+				self.environment.path_policy.readable(&path)?;
 			}
-			if path.is_file() {
-				let mut add_file = false;
-				match file_filter {
-					Some(ref filter) => {
-						add_file = filter.contains(
-							&path
-								.extension()
-								.unwrap_or_default()
-								.to_str()
-								.unwrap_or_default()
-								.to_string(),
-						);
-					}
-
-					None => {
-						add_file = true;
-					}
-				}
-
-				if add_file {
-					path_vec.push(path.clone());
-				}
+			if path.is_dir() && recursive && options.include_dir(&relative) {
+				path_vec.append(&mut self.walk_dir(path.clone(), relative.clone(), true, options)?)
+			}
+			if path.is_file() && options.include_file(&relative) {
+				path_vec.push(path.clone());
 			}
 		}
 
 		Ok(path_vec)
 	}
+
+	/*
+	 * Rewriting a file with the same contents would still make everything
+	 * including it rebuild, those are left alone.
+	 */
+	pub fn write(
+		&self,
+		path: &str,
+		contents: &[u8],
+	) -> anyhow::Result<PathBuf> {
+		let path = self.writable(path)?;
+		if fs::read(&path).is_ok_and(|existing| existing == contents) {
+			return Ok(path);
+		}
+
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(&path, contents)
+			.with_context(|| format!("Couldn't write {}!", path.display()))?;
+
+		Ok(path)
+	}
+
+	pub fn copy(
+		&self,
+		from: &str,
+		to: &str,
+	) -> anyhow::Result<PathBuf> {
+		let from = self.readable(from)?;
+		let to = self.writable(to)?;
+		if let Some(parent) = to.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::copy(&from, &to).with_context(|| {
+			format!("Couldn't copy {} to {}!", from.display(), to.display())
+		})?;
+
+		Ok(to)
+	}
+
+	/*
+	 * `path` relative to `base`, the project directory by default.
+	 */
+	pub fn relative(
+		&self,
+		path: &str,
+		base: Option<&str>,
+	) -> PathBuf {
+		let project = &self.environment.project_directory;
+		let path = PathPolicy::resolve(&project.join(path));
+		let base = PathPolicy::resolve(&project.join(base.unwrap_or(".")));
+
+		diff_paths(&path, &base).unwrap_or(path)
+	}
 }
 
 impl UserData for Filesystem {
 	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
 		methods.add_method_mut(
 			"walk",
-			|_, this, (path, recursive, filter): (String, bool, Option<Vec<String>>)| match this
-				.walk_dir(dunce::canonicalize(path)?, recursive, filter)
+			|_, this, (path, recursive, options): (String, bool, WalkOptions)| match this
+				.walk_dir(
+					this.environment
+						.path_policy
+						.readable(dunce::canonicalize(path)?)
+						.map_err(mlua::Error::external)?,
+					PathBuf::new(),
+					recursive,
+					&options,
+				)
 			{
 				Ok(paths) => {
 					let ret: Vec<String> = paths
@@ -76,6 +158,45 @@ impl UserData for Filesystem {
 
 				Err(e) => Err(mlua::Error::external(e)),
 			},
-		)
+		);
+
+		methods.add_method("exists", |_, this, path: String| {
+			Ok(this.readable(&path).map_err(into_lua_error)?.exists())
+		});
+
+		methods.add_method("read", |_, this, path: String| {
+			let path = this.readable(&path).map_err(into_lua_error)?;
+			fs::read_to_string(&path)
+				.with_context(|| format!("Couldn't read {}!", path.display()))
+				.map_err(into_lua_error)
+		});
+
+		methods.add_method(
+			"write",
+			|_, this, (path, contents): (String, mlua::String)| {
+				this.write(&path, &contents.as_bytes())
+					.map(|path| path.display().to_string())
+					.map_err(into_lua_error)
+			},
+		);
+
+		methods.add_method("mkdir", |_, this, path: String| {
+			let path = this.writable(&path).map_err(into_lua_error)?;
+			fs::create_dir_all(&path)?;
+			Ok(path.display().to_string())
+		});
+
+		methods.add_method("copy", |_, this, (from, to): (String, String)| {
+			this.copy(&from, &to)
+				.map(|path| path.display().to_string())
+				.map_err(into_lua_error)
+		});
+
+		methods.add_method(
+			"relative",
+			|_, this, (path, base): (String, Option<String>)| {
+				Ok(this.relative(&path, base.as_deref()).display().to_string())
+			},
+		);
 	}
 }
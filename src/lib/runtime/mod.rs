@@ -1,28 +1,34 @@
-use crate::lib::data::flag_type::FlagType;
-use crate::lib::data::project_language::ProjectLanguage;
-use crate::lib::data::project_type::ProjectType;
+use crate::lib::data::pin::Pin;
 use crate::lib::util::build_cache::BuildCache;
-use crate::lib::util::either::Either;
+use crate::lib::util::diagnostics::Diagnostics;
+use crate::lib::util::lockfile::Lockfile;
+use crate::lib::util::script_cache::ScriptCache;
+use crate::lib::util::script_error::ScriptError;
+use crate::lib::util::timings::Timings;
 use crate::lib::{
-	compilers::{
-		generic, generic::Generic, mingw, mingw::MinGW, msvc, msvc::MSVC,
-	},
-	data::{environment::Environment, project::Project},
+	data::environment::Environment,
 	runtime::{
-		filesystem::Filesystem, network::Network, storage::Storage,
-		system::System, task_manager::TaskManager,
+		network::Network, subproject::ProjectScope, system::System,
+		task_manager::TaskManager,
 	},
 	ui::UI,
 	util::cache::Cache,
 };
-use clap::builder::TypedValueParser;
-use mlua::{Compiler, Lua, ObjectLike, Table, Value};
+use crate::lib::ui::message::Message;
+use anyhow::anyhow;
+use crate::lib::util::error::NuMakeError::ScriptTimeLimit;
+use mlua::{Lua, VmState};
+use pathdiff::diff_paths;
+use std::cell::Cell;
 use std::fs;
-use std::str::FromStr;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub mod filesystem;
+pub mod modules;
 pub mod network;
 pub mod storage;
+pub mod subproject;
 pub mod system;
 pub mod task_manager;
 
@@ -30,17 +36,17 @@ pub struct Runtime {
 	// Tools
 	task_manager: task_manager::TaskManager,
 	network: network::Network,
-	storage: storage::Storage,
-	filesystem: filesystem::Filesystem,
 	system: system::System,
 
-	// Compilers
-	msvc: msvc::MSVC,
-	mingw: mingw::MinGW,
-	generic: generic::Generic,
+	// The root project's globals.
+	scope: subproject::ProjectScope,
 
 	ui: UI,
 	cache: Cache,
+	script_cache: ScriptCache,
+	lockfile: Lockfile,
+	diagnostics: Diagnostics,
+	timings: Timings,
 	environment: Environment,
 
 	lua: Lua,
@@ -53,110 +59,127 @@ impl Runtime {
 	) -> anyhow::Result<Self> {
 		let cache: Cache = Cache::new(environment.clone())?;
 		let build_cache: BuildCache = BuildCache::new(environment.clone())?;
-		let system = System::new(ui.clone());
+		let diagnostics = Diagnostics::new();
+		let timings = Timings::new(environment.timings.is_some());
+		let system =
+			System::new(ui.clone(), diagnostics.clone(), timings.clone());
+		let script_cache = ScriptCache::new(cache.clone(), environment.script_cache);
+		let lockfile =
+			Lockfile::new(&environment.project_file, environment.update.clone())?;
+
+		let task_manager = TaskManager::new(environment.project_directory.clone());
+		let network = Network::new(
+			environment.clone(),
+			ui.clone(),
+			cache.clone(),
+			lockfile.clone(),
+		);
+		/*
+		 * Luau's sandbox makes the standard library read-only, scripts
+		 * can't break it for each other or for numake.
+		 */
+		let lua = Lua::new();
+		lua.sandbox(true)?;
+		lua.set_app_data(environment.path_policy.clone());
+		let memory_limit = environment.config.script.memory_limit.0;
+		if memory_limit > 0 {
+			lua.set_memory_limit(memory_limit as usize)?;
+		}
+		Self::limit_time(&lua, environment.config.script.time_limit);
+
+		let scope = ProjectScope::new(
+			environment.clone(),
+			task_manager.clone(),
+			network.clone(),
+			ui.clone(),
+			cache.clone(),
+			build_cache,
+			script_cache.clone(),
+			system.clone(),
+		);
 
 		Ok(Runtime {
-			task_manager: TaskManager::new(),
-			network: Network::new(
-				environment.clone(),
-				ui.clone(),
-				cache.clone(),
-			),
-			storage: Storage::new(cache.clone()),
-			filesystem: Filesystem::new(environment.clone()),
-			msvc: MSVC::new(
-				environment.clone(),
-				build_cache.clone(),
-				ui.clone(),
-				system.clone(),
-			),
-			mingw: MinGW::new(
-				environment.clone(),
-				build_cache.clone(),
-				ui.clone(),
-				system.clone(),
-			),
-			generic: Generic::new(
-				environment.clone(),
-				build_cache.clone(),
-				ui.clone(),
-				system.clone(),
-			),
+			task_manager,
+			network,
+			scope,
 			system,
+			script_cache,
 			cache,
+			lockfile,
+			diagnostics,
+			timings,
 			ui,
 			environment,
-			lua: Lua::new(),
+			lua,
 		})
 	}
 
+	/*
+	 * Luau calls the interrupt at every function call and loop iteration,
+	 * the time between two of them is time spent running Lua. Compilers
+	 * and downloads run in between as well, a gap longer than a step is
+	 * one of those and only counts as a step. Covers the script and every
+	 * task it runs.
+	 */
+	fn limit_time(
+		lua: &Lua,
+		time_limit: u64,
+	) {
+		const MAX_STEP: Duration = Duration::from_millis(10);
+		if time_limit == 0 {
+			return;
+		}
+
+		let limit = Duration::from_secs(time_limit);
+		let clock = Cell::new((Duration::ZERO, Instant::now()));
+		lua.set_interrupt(move |_| {
+			let (used, last) = clock.get();
+			let now = Instant::now();
+			let used = used + (now - last).min(MAX_STEP);
+			clock.set((used, now));
+
+			match used > limit {
+				true => Err(mlua::Error::external(ScriptTimeLimit(time_limit))),
+				false => Ok(VmState::Continue),
+			}
+		});
+	}
+
 	pub(crate) fn execute_script(
 		&mut self,
 		filename: &String,
 	) -> anyhow::Result<()> {
-		let file_size = fs::metadata(filename)?.len();
-		let mut should_compile = self.cache.get_value(filename)
-			!= Some(toml::Value::from(file_size.to_string()));
-		let mut chunk: Vec<u8> = Vec::new();
-
-		if self.cache.check_file_exists(filename) {
-			chunk = self.cache.read_file(filename)?;
-		} else {
-			should_compile = true;
-		}
+		let _span = self.system.span("script", filename.clone());
+		let chunk_name = diff_paths(filename, &self.environment.root_directory)
+			.unwrap_or(Path::new(filename).to_path_buf());
+		let chunk = self
+			.script_cache
+			.compile(&chunk_name.display().to_string(), &fs::read(filename)?)
+			.map_err(|e| match e.downcast::<mlua::Error>() {
+				Ok(e) => anyhow!(ScriptError::new(e, None)),
+				Err(e) => e,
+			})?;
+
+		let globals = self.scope.globals(&self.lua)?;
 
-		self.push_globals(&self.lua.globals())?;
-
-		if should_compile {
-			chunk = fs::read(filename)?;
-			let compiler = Compiler::new()
-				.set_optimization_level(2)
-				.set_coverage_level(2);
-			chunk = compiler.compile(chunk)?;
-			self.cache.write_file(filename, chunk.clone())?;
-			self.cache.set_value(
-				filename,
-				toml::Value::from(chunk.len().to_string()),
-			)?;
-		}
-
-		self.lua.load(chunk).exec()?;
+		self.lua
+			.load(chunk)
+			.set_name(format!("@{}", chunk_name.display()))
+			.set_environment(globals)
+			.exec()
+			.map_err(|e| ScriptError::new(e, None))?;
 
 		self.cache.flush()?;
 
 		Ok(())
 	}
 
-	fn push_globals(
-		&self,
-		globals: &Table,
-	) -> anyhow::Result<()> {
-		globals.set(
-			"new_project",
-			self.lua.create_function(
-				|_, (name, language): (String, ProjectLanguage)| {
-					Ok(Project {
-						name,
-						language,
-						..Default::default()
-					})
-				},
-			)?,
-		)?;
-
-		
-
-		globals.set("storage", self.storage.clone())?;
-		self.lua
-			.globals()
-			.set("filesystem", self.filesystem.clone())?;
-		globals.set("tasks", self.task_manager.clone())?;
-		globals.set("network", self.network.clone())?;
-		globals.set("msvc", self.msvc.clone())?;
-		globals.set("mingw", self.mingw.clone())?;
-		globals.set("generic", self.generic.clone())?;
+	pub fn get_fetched(&self) -> Vec<(String, Pin)> {
+		self.network.get_fetched()
+	}
 
-		Ok(())
+	pub fn get_lockfile(&self) -> Lockfile {
+		self.lockfile.clone()
 	}
 
 	pub fn get_tasks(&mut self) -> Vec<String> {
@@ -167,6 +190,39 @@ impl Runtime {
 		&mut self,
 		task: &str,
 	) -> anyhow::Result<()> {
-		self.task_manager.run(task)
+		self.ui.emit(Message::TaskStarted {
+			task: task.to_string(),
+		});
+
+		let start = Instant::now();
+		let span = self.system.span("task", task);
+		let result = self.task_manager.run(task);
+		drop(span);
+
+		self.ui.emit(Message::TaskFinished {
+			task: task.to_string(),
+			success: result.is_ok(),
+			duration_ms: start.elapsed().as_millis() as u64,
+		});
+
+		result
+	}
+
+	/*
+	 * Summaries printed once the build task has finished, even when it failed.
+	 */
+	pub fn report(&self) -> anyhow::Result<()> {
+		self.diagnostics
+			.report(&self.ui, self.environment.diagnostic_format)?;
+
+		if let Some(count) = self.environment.timings {
+			self.timings.report(
+				&self.ui,
+				count,
+				&self.environment.numake_directory,
+			)?;
+		}
+
+		Ok(())
 	}
 }
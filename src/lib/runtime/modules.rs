@@ -0,0 +1,193 @@
+use crate::lib::data::environment::Environment;
+use crate::lib::util::error::NuMakeError::{
+	InvalidModulePath,
+	ModuleNotFound,
+	RequireCycle,
+};
+use crate::lib::util::into_lua_error;
+use crate::lib::util::script_cache::ScriptCache;
+use anyhow::{anyhow, Context};
+use mlua::{Function, Lua, RegistryKey, Table, Value};
+use pathdiff::diff_paths;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/*
+ * `require("cmake/toolchains")` for scripts split across files. Names are
+ * resolved relative to the project directory, then to the module paths
+ * from numake.toml, and can never point outside of them. Every module
+ * runs once, later requires get the value it returned. Modules see the
+ * globals of the project that required them.
+ */
+#[derive(Clone)]
+pub struct Modules {
+	environment: Environment,
+	script_cache: ScriptCache,
+
+	loaded: Arc<Mutex<HashMap<PathBuf, RegistryKey>>>,
+	// Modules currently running, innermost last.
+	loading: Arc<Mutex<Vec<(String, PathBuf)>>>,
+}
+
+impl Modules {
+	pub fn new(
+		environment: Environment,
+		script_cache: ScriptCache,
+	) -> Self {
+		Modules {
+			environment,
+			script_cache,
+			loaded: Arc::new(Mutex::new(HashMap::new())),
+			loading: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	fn roots(&self) -> Vec<PathBuf> {
+		let project = &self.environment.project_directory;
+
+		std::iter::once(project.clone())
+			.chain(
+				self.environment
+					.config
+					.modules
+					.paths
+					.iter()
+					.map(|path| project.join(path)),
+			)
+			.collect()
+	}
+
+	fn resolve(
+		&self,
+		name: &str,
+	) -> anyhow::Result<PathBuf> {
+		let mut relative = PathBuf::new();
+		for component in Path::new(name).components() {
+			match component {
+				Component::Normal(part) => relative.push(part),
+				Component::CurDir => {}
+				_ => return Err(anyhow!(InvalidModulePath(name.to_string()))),
+			}
+		}
+
+		let candidates: Vec<PathBuf> = match relative.extension() {
+			Some(extension) if extension == "lua" || extension == "luau" => {
+				vec![relative.clone()]
+			}
+			_ => ["luau", "lua"]
+				.iter()
+				.map(|extension| relative.with_extension(extension))
+				.chain(
+					["init.luau", "init.lua"]
+						.iter()
+						.map(|file| relative.join(file)),
+				)
+				.collect(),
+		};
+
+		let roots = self.roots();
+		for root in &roots {
+			for candidate in &candidates {
+				let path = root.join(candidate);
+				if !path.is_file() {
+					continue;
+				}
+
+				// A symlink mustn't lead out of the root either.
+				let path = dunce::canonicalize(&path)?;
+				if !path.starts_with(dunce::canonicalize(root)?) {
+					return Err(anyhow!(InvalidModulePath(name.to_string())));
+				}
+
+				return Ok(path);
+			}
+		}
+
+		Err(anyhow!(ModuleNotFound {
+			name: name.to_string(),
+			searched: roots
+				.iter()
+				.map(|root| root.display().to_string())
+				.collect::<Vec<_>>()
+				.join(", "),
+		}))
+	}
+
+	fn load(
+		&self,
+		lua: &Lua,
+		globals: &Table,
+		name: &str,
+	) -> anyhow::Result<Value> {
+		let path = self.resolve(name)?;
+
+		if let Some(key) = (*self.loaded.lock().unwrap()).get(&path) {
+			return Ok(lua.registry_value(key)?);
+		}
+
+		{
+			let mut loading = self.loading.lock().unwrap();
+			if let Some(start) = loading.iter().position(|(_, loading)| *loading == path) {
+				let chain: Vec<&str> = loading[start..]
+					.iter()
+					.map(|(name, _)| name.as_str())
+					.chain(std::iter::once(name))
+					.collect();
+				return Err(anyhow!(RequireCycle(chain.join(" -> "))));
+			}
+			loading.push((name.to_string(), path.clone()));
+		}
+
+		let result = self.run(lua, globals, name, &path);
+		(*self.loading.lock().unwrap()).pop();
+
+		// Like Lua's require, a module without a return value still counts as loaded.
+		let value = match result? {
+			Value::Nil => Value::Boolean(true),
+			value => value,
+		};
+		(*self.loaded.lock().unwrap()).insert(path, lua.create_registry_value(value.clone())?);
+
+		Ok(value)
+	}
+
+	fn run(
+		&self,
+		lua: &Lua,
+		globals: &Table,
+		name: &str,
+		path: &Path,
+	) -> anyhow::Result<Value> {
+		let chunk_name = diff_paths(path, &self.environment.root_directory)
+			.unwrap_or(path.to_path_buf());
+		let source = fs::read(path)
+			.with_context(|| format!("Couldn't read module {}!", path.display()))?;
+		let bytecode = self
+			.script_cache
+			.clone()
+			.compile(&chunk_name.display().to_string(), &source)?;
+
+		Ok(lua
+			.load(bytecode)
+			.set_name(format!("@{}", chunk_name.display()))
+			.set_environment(globals.clone())
+			.call(name)?)
+	}
+
+	/*
+	 * The global `require` function.
+	 */
+	pub fn create_require(
+		&self,
+		lua: &Lua,
+		globals: &Table,
+	) -> mlua::Result<Function> {
+		let modules = self.clone();
+		let globals = globals.clone();
+		lua.create_function(move |lua, name: String| {
+			modules.load(lua, &globals, &name).map_err(into_lua_error)
+		})
+	}
+}
@@ -1,17 +1,55 @@
+use crate::lib::data::download_options::DownloadOptions;
 use crate::lib::data::environment::Environment;
+use crate::lib::data::pin::Pin;
 use crate::lib::ui::format::info::Info;
-use crate::lib::ui::format::ok;
+use crate::lib::ui::format::{error, ok};
+use crate::lib::ui::message::Message;
 use crate::lib::ui::UI;
 use crate::lib::util::cache::Cache;
+use crate::lib::util::download::Downloader;
+use crate::lib::util::git::Git;
+use crate::lib::util::lockfile::Lockfile;
+use crate::lib::util::patch::Patcher;
+use crate::lib::util::into_lua_error;
+use crate::lib::util::archive::{ArchiveFormat, Extractor};
+use crate::lib::util::error::NuMakeError;
+use crate::lib::util::error::NuMakeError::{
+	ArchiveSubdirNotFound,
+	ChecksumMismatch,
+	GitRevisionNotFound,
+	InvalidGitArgument,
+	NetworkDenied,
+	NetworkHostNotAllowed,
+	OfflineNotCached,
+	UnknownArchiveFormat,
+};
+use anyhow::anyhow;
+use dialoguer::Confirm;
 use mlua::{UserData, UserDataMethods};
-use std::io::Cursor;
-use zip::ZipArchive;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/*
+ * Where verified download contents are read from.
+ */
+enum Contents {
+	// A blob in the cache, by sha256.
+	Stored(String),
+	// A file:// source, read in place.
+	Local(PathBuf),
+}
 
 #[derive(Clone)]
 pub struct Network {
 	environment: Environment,
 	ui: UI,
 	cache: Cache,
+	lockfile: Lockfile,
+
+	// Every source fetched during this run along with what it resolved to.
+	fetched: Arc<Mutex<Vec<(String, Pin)>>>,
 }
 
 impl Network {
@@ -19,84 +57,836 @@ impl Network {
 		environment: Environment,
 		ui: UI,
 		cache: Cache,
+		lockfile: Lockfile,
 	) -> Network {
 		Network {
 			environment,
 			ui,
 			cache,
+			lockfile,
+			fetched: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	/*
+	 * The same network for a subproject, everything fetched still ends
+	 * up in one list.
+	 */
+	pub fn for_environment(
+		&self,
+		environment: Environment,
+	) -> Network {
+		Network {
+			environment,
+			..self.clone()
+		}
+	}
+
+	pub fn get_fetched(&self) -> Vec<(String, Pin)> {
+		(*self.fetched.lock().unwrap()).clone()
+	}
+
+	/*
+	 * "https://github.com/..." and "git@github.com:..." -> "github.com".
+	 * Local paths have no host.
+	 */
+	fn host(source: &str) -> Option<String> {
+		if let Ok(url) = reqwest::Url::parse(source) {
+			return url.host_str().map(String::from);
+		}
+
+		let (remote, _) = source.split_once(':')?;
+		let host = remote.rsplit('@').next()?;
+		match host.is_empty() || host.contains('/') {
+			true => None,
+			false => Some(host.to_string()),
 		}
 	}
-	pub unsafe fn download_zip(
+
+	/*
+	 * Hosts allowed in numake.toml are contacted right away, any other is
+	 * asked about once and the answer kept in the cache. Without a
+	 * terminal to ask on there's nobody to allow it.
+	 */
+	fn check_host(
+		&mut self,
+		url: &str,
+		source: &str,
+	) -> anyhow::Result<()> {
+		let Some(host) = Self::host(source) else {
+			return Ok(());
+		};
+		if self.environment.deny_network {
+			return Err(anyhow!(NetworkDenied(url.to_string())));
+		}
+
+		// Mirrors come from numake.toml, they're declared already.
+		if source != url || self.environment.config.network.allows(&host) {
+			return Ok(());
+		}
+
+		let key = format!("network:{}", host);
+		let allowed = match self.cache.get_value(&key).and_then(|value| value.as_bool()) {
+			Some(allowed) => allowed,
+			None if !io::stdin().is_terminal() || !io::stderr().is_terminal() => false,
+			None => {
+				let allowed = Confirm::new()
+					.with_prompt(format!("Allow the script to download from {}? [{}]", host, url))
+					.default(false)
+					.interact()?;
+				self.cache.set_value(&key, toml::Value::from(allowed))?;
+				self.cache.flush()?;
+				allowed
+			}
+		};
+
+		match allowed {
+			true => Ok(()),
+			false => Err(anyhow!(NetworkHostNotAllowed {
+				host,
+				url: url.to_string(),
+			})),
+		}
+	}
+
+	fn checksum_key(url: &str) -> String {
+		"sha256:".to_string() + url
+	}
+
+	/*
+	 * Returns the sha256, or an error if it doesn't match the pinned one.
+	 * With --update-hashes mismatches are only reported so the new hashes
+	 * can be printed at the end.
+	 */
+	fn verify(
+		&self,
+		url: &str,
+		actual: String,
+		expected: &Option<String>,
+	) -> anyhow::Result<String> {
+		match expected {
+			Some(expected) if *expected != actual => {
+				let err = ChecksumMismatch {
+					url: url.to_string(),
+					expected: expected.clone(),
+					actual: actual.clone(),
+				};
+
+				if self.environment.update_hashes {
+					self.ui.println(err.to_string(), error::Error::default());
+					Ok(actual)
+				} else {
+					Err(anyhow!(err))
+				}
+			}
+
+			_ => Ok(actual),
+		}
+	}
+
+	/*
+	 * The downloaded contents and their sha256, verified against the
+	 * pinned hash. A cached copy that doesn't match is downloaded again.
+	 */
+	fn fetch(
+		&mut self,
+		url: &str,
+		expected: &Option<String>,
+		refresh: bool,
+	) -> anyhow::Result<(Contents, String)> {
+		// Pinned contents count no matter which URL or project stored them.
+		if let Some(sha256) = expected.as_ref().filter(|sha256| self.cache.has_blob(sha256)) {
+			self.ui.emit(Message::CacheHit {
+				key: url.to_string(),
+			});
+			return Ok((Contents::Stored(sha256.clone()), sha256.clone()));
+		}
+
+		if let Some(cached) = self.cache.get_file_hash(url).filter(|_| !refresh) {
+			match self.verify(url, cached.clone(), expected) {
+				Ok(hash) => {
+					self.ui.emit(Message::CacheHit {
+						key: url.to_string(),
+					});
+					return Ok((Contents::Stored(cached), hash));
+				}
+
+				Err(_) => self.ui.println(
+					format!(
+						"Cached download doesn't match the pinned sha256, downloading it again. [{}]",
+						url
+					),
+					Info::default(),
+				),
+			}
+		}
+
+		let source = self.environment.config.mirror(url);
+		if source != url {
+			self.ui.println(
+				format!("Using mirror {} [{}]", source, url),
+				Info::default(),
+			);
+		}
+
+		// Local files are read in place, there's no point caching them.
+		if source.starts_with("file://") {
+			let path = reqwest::Url::parse(&source)?
+				.to_file_path()
+				.map_err(|_| anyhow!("Invalid file URL {}!", source))?;
+			// Mirrors come from numake.toml, only the script's own paths are checked.
+			if source == url {
+				self.environment.path_policy.readable(&path)?;
+			}
+			let actual = sha256::try_digest(&path).map_err(|err| {
+				anyhow!("Couldn't read {}! {}", path.display(), err)
+			})?;
+			let hash = self.verify(url, actual, expected)?;
+			return Ok((Contents::Local(path), hash));
+		}
+
+		if self.environment.offline {
+			return Err(anyhow!(OfflineNotCached(url.to_string())));
+		}
+		self.check_host(url, &source)?;
+
+		// Another project may be downloading the same file into the shared
+		// store, it's stored under the URL once that one is done.
+		let before = self.cache.get_file_hash(url);
+		let _lock = self.cache.lock_partial(&source, || {
+			self.ui.println(
+				format!("Waiting for another numake to finish downloading {}", url),
+				Info::default(),
+			)
+		})?;
+		if let Some(stored) = self.cache.get_file_hash(url).filter(|hash| Some(hash) != before.as_ref()) {
+			if let Ok(hash) = self.verify(url, stored.clone(), expected) {
+				return Ok((Contents::Stored(stored), hash));
+			}
+		}
+
+		// Kept on failure or Ctrl-C, the next run resumes it.
+		let partial = self.cache.get_partial_path(&source);
+		Downloader::new(&self.environment.config.download, &self.ui)
+			.fetch(&source, &partial)?;
+
+		if let Err(err) = self.verify(url, sha256::try_digest(&partial)?, expected) {
+			fs::remove_file(&partial)?;
+			return Err(err);
+		}
+		let hash = self.cache.store_file(url, &partial)?;
+
+		Ok((Contents::Stored(hash.clone()), hash))
+	}
+
+	/*
+	 * A file to read the contents from. Blobs that are stored compressed
+	 * are decompressed into a temporary file first.
+	 */
+	fn open_contents(
+		&self,
+		contents: &Contents,
+	) -> anyhow::Result<File> {
+		let sha256 = match contents {
+			Contents::Local(path) => return Ok(File::open(path)?),
+			Contents::Stored(sha256) => sha256,
+		};
+
+		if let Some(path) = self.cache.get_blob_path(sha256) {
+			return Ok(File::open(path)?);
+		}
+
+		let mut file = tempfile::tempfile_in(&self.environment.numake_directory)?;
+		io::copy(&mut self.cache.open_blob(sha256)?, &mut file)?;
+		file.rewind()?;
+
+		Ok(file)
+	}
+
+	/*
+	 * The hash a download has to match: the one pinned in the script,
+	 * otherwise the one in numake.lock.
+	 */
+	fn expected_hash(
+		&self,
+		name: &str,
+		url: &str,
+		options: &DownloadOptions,
+	) -> Option<String> {
+		options.sha256.clone().or_else(|| {
+			self.lockfile
+				.get(name, url, None)
+				.and_then(|entry| entry.sha256)
+		})
+	}
+
+	fn lock_hint(
+		err: anyhow::Error,
+		name: &str,
+		locked: bool,
+	) -> anyhow::Error {
+		match err.downcast_ref::<NuMakeError>() {
+			Some(ChecksumMismatch { url, .. }) if locked => {
+				let hint = format!(
+					"{} doesn't match numake.lock, run `numake update {}` if the new contents are expected.",
+					url, name
+				);
+				err.context(hint)
+			}
+
+			_ => err,
+		}
+	}
+
+	/*
+	 * Records what a source resolved to for `numake fetch` and in
+	 * numake.lock.
+	 */
+	fn pinned(
+		&mut self,
+		name: &str,
+		url: &str,
+		rev: Option<&str>,
+		pin: Pin,
+	) -> anyhow::Result<()> {
+		if let Some(previous) = self.lockfile.record(name, url, rev, &pin)? {
+			self.ui.println(
+				format!("Updated {}: {} -> {}", name, previous, pin),
+				Info::default(),
+			);
+		}
+
+		(*self.fetched.lock().unwrap()).push((url.to_string(), pin));
+		Ok(())
+	}
+
+	/*
+	 * Path and hash of a previous result for the key if its recorded hash
+	 * is still the expected one. The hash is only recorded once extraction
+	 * or download has finished, anything without one is a leftover of an
+	 * interrupted run.
+	 */
+	fn cached_result(
+		&mut self,
+		key: &str,
+		path: &Path,
+		expected: &Option<String>,
+	) -> anyhow::Result<Option<(String, String)>> {
+		let Some(hash) = self
+			.cache
+			.get_value(&Self::checksum_key(key))
+			.and_then(|value| value.as_str().map(|str| str.to_string()))
+		else {
+			return Ok(None);
+		};
+
+		if !path.exists() || expected.as_ref().is_some_and(|sha256| *sha256 != hash) {
+			return Ok(None);
+		}
+
+		// Saves the last use for `numake cache prune`.
+		self.cache.flush()?;
+		self.ui.emit(Message::CacheHit {
+			key: key.to_string(),
+		});
+
+		Ok(Some((path.to_str().unwrap_or("ERROR").to_string(), hash)))
+	}
+
+	fn record_result(
+		&mut self,
+		key: &str,
+		hash: &str,
+	) -> anyhow::Result<()> {
+		self.cache
+			.set_value(&Self::checksum_key(key), toml::Value::from(hash))?;
+		self.cache.flush()?;
+
+		Ok(())
+	}
+
+	/*
+	 * The patch files and a suffix for cache keys, so changing a patch
+	 * gets a freshly extracted tree to apply it to.
+	 */
+	fn load_patches(
+		&self,
+		options: &DownloadOptions,
+	) -> anyhow::Result<(Vec<PathBuf>, String)> {
+		if options.patches.is_empty() {
+			return Ok((Vec::new(), String::new()));
+		}
+
+		let mut paths = Vec::new();
+		let mut contents = Vec::new();
+		for patch in &options.patches {
+			let path = self
+				.environment
+				.path_policy
+				.readable(self.environment.project_directory.join(patch))?;
+			let data = fs::read(&path).map_err(|err| {
+				anyhow!("Couldn't read patch {}! {}", path.display(), err)
+			})?;
+
+			contents.extend_from_slice(patch.as_bytes());
+			contents.extend_from_slice(&data);
+			paths.push(path);
+		}
+
+		Ok((paths, format!("#patches={}", sha256::digest(&contents))))
+	}
+
+	/*
+	 * Runs on a fresh tree only, a failure throws the tree away so the
+	 * next run starts over.
+	 */
+	fn apply_patches(
+		&self,
+		patches: &[PathBuf],
+		root: &Path,
+	) -> anyhow::Result<()> {
+		for patch in patches {
+			let result = Patcher { patch, root }.apply();
+			let changed = result.inspect_err(|_| {
+				let _ = fs::remove_dir_all(root);
+			})?;
+
+			self.ui.println(
+				format!("Applied {} ({} files changed)", patch.display(), changed),
+				ok::Ok::default(),
+			);
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * Extracts the archive into a cache directory and returns its path.
+	 * Without a format it's detected from the magic bytes or extension.
+	 */
+	pub fn download_archive(
 		&mut self,
 		url: String,
+		options: DownloadOptions,
+		format: Option<ArchiveFormat>,
 	) -> anyhow::Result<String> {
-		if self.cache.check_dir_exists(&url) {
-			self.ui
-				.println("Archive contents found on disk.", Info::default());
-			Ok(self
+		let (patches, patches_key) = self.load_patches(&options)?;
+		let key = options.extract_key(&url) + &patches_key;
+		let name = options.name.clone().unwrap_or(Lockfile::default_name(&url));
+		let expected = self.expected_hash(&name, &url, &options);
+		let refresh = self.lockfile.should_refresh(&name, &url);
+
+		if !refresh && self.cache.check_dir_exists(&key) {
+			let path = self.cache.get_dir(&key)?;
+			if let Some((path, hash)) = self.cached_result(&key, &path, &expected)? {
+				self.ui.println(
+					"Archive contents found on disk.",
+					Info::default(),
+				);
+				self.pinned(&name, &url, None, Pin::Sha256(hash))?;
+				return Ok(path);
+			}
+		}
+
+		if self.environment.skip_downloads {
+			return Ok(self.cache.dir_path(&key).to_str().unwrap_or("ERROR").to_string());
+		}
+
+		let locked = options.sha256.is_none() && expected.is_some();
+		let (contents, hash) = self
+			.fetch(&url, &expected, refresh)
+			.map_err(|err| Self::lock_hint(err, &name, locked))?;
+		let mut file = self.open_contents(&contents)?;
+
+		// Enough for the magic bytes and the ustar marker at 257.
+		let mut head = Vec::new();
+		(&mut file).take(512).read_to_end(&mut head)?;
+		file.rewind()?;
+
+		let format = format
+			.or_else(|| ArchiveFormat::detect(&url, &head))
+			.ok_or_else(|| UnknownArchiveFormat(url.clone()))?;
+
+		let spinner = self
+			.ui
+			.create_spinner(format!("Extracting archive... [{}]", &url));
+
+		let path = self.cache.get_dir(&key)?;
+		fs::remove_dir_all(&path)?;
+		fs::create_dir_all(&path)?;
+
+		let extractor = Extractor {
+			url: url.clone(),
+			limits: self.environment.config.archive.clone(),
+			strip_components: options.strip_components,
+			subdir: options.subdir.clone().map(PathBuf::from),
+		};
+		let result = extractor.extract(file, format, &path);
+		spinner.finish_and_clear();
+
+		// Don't leave half of a rejected archive lying around.
+		let extracted = result.inspect_err(|_| {
+			let _ = fs::remove_dir_all(&path);
+		})?;
+
+		if extracted == 0 {
+			if let Some(subdir) = options.subdir {
+				return Err(anyhow!(ArchiveSubdirNotFound { url, subdir }));
+			}
+		}
+
+		self.apply_patches(&patches, &path)?;
+
+		self.record_result(&key, &hash)?;
+		self.pinned(&name, &url, None, Pin::Sha256(hash))?;
+
+		self.ui.println(
+			format!("Done extracting! [{}]", url),
+			ok::Ok::default(),
+		);
+
+		Ok(path.to_str().unwrap().to_string())
+	}
+
+	/*
+	 * Single files (headers, dlls, tools) are kept as-is under the name
+	 * the URL ends with.
+	 */
+	pub fn download_file(
+		&mut self,
+		url: String,
+		options: DownloadOptions,
+	) -> anyhow::Result<String> {
+		let file_name = reqwest::Url::parse(&url)?
+			.path_segments()
+			.and_then(|mut segments| segments.next_back())
+			.filter(|name| !name.is_empty())
+			.unwrap_or("download")
+			.to_string();
+
+		let name = options.name.clone().unwrap_or(Lockfile::default_name(&url));
+		let expected = self.expected_hash(&name, &url, &options);
+		let refresh = self.lockfile.should_refresh(&name, &url);
+
+		let directory = match self.environment.skip_downloads {
+			true => self.cache.dir_path(&url),
+			false => self.cache.get_dir(&url)?,
+		};
+		let path = directory.join(file_name);
+		if !refresh {
+			if let Some((path, hash)) = self.cached_result(&url, &path, &expected)? {
+				self.pinned(&name, &url, None, Pin::Sha256(hash))?;
+				return Ok(path);
+			}
+		}
+
+		if self.environment.skip_downloads {
+			return Ok(path.to_str().unwrap_or("ERROR").to_string());
+		}
+
+		let locked = options.sha256.is_none() && expected.is_some();
+		let (contents, hash) = self
+			.fetch(&url, &expected, refresh)
+			.map_err(|err| Self::lock_hint(err, &name, locked))?;
+		io::copy(&mut self.open_contents(&contents)?, &mut File::create(&path)?)?;
+
+		self.record_result(&url, &hash)?;
+		self.pinned(&name, &url, None, Pin::Sha256(hash))?;
+
+		Ok(path.to_str().unwrap().to_string())
+	}
+
+	/*
+	 * Local repositories are given as paths relative to the project,
+	 * everything else goes through the mirror table like downloads do.
+	 */
+	fn git_source(
+		&self,
+		url: &str,
+	) -> anyhow::Result<String> {
+		let path = self.environment.project_directory.join(url);
+		if !url.contains("://") && path.exists() {
+			let path = self.environment.path_policy.readable(&path)?;
+			return Ok(path.to_str().unwrap_or(url).to_string());
+		}
+
+		Ok(self.environment.config.mirror(url))
+	}
+
+	fn resolve_git_rev(
+		&mut self,
+		url: &str,
+		source: &str,
+		repo: &Path,
+		rev: &str,
+	) -> anyhow::Result<String> {
+		let is_hash = rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit());
+		let candidates = [
+			format!("refs/remotes/origin/{rev}"),
+			format!("refs/tags/{rev}"),
+			rev.to_string(),
+		];
+		let resolve_local = || {
+			candidates
+				.iter()
+				.find_map(|candidate| Git::resolve(repo, candidate))
+		};
+
+		// A full hash can't change, no need to ask the server.
+		if is_hash {
+			if let Some(commit) = Git::resolve(repo, rev) {
+				return Ok(commit);
+			}
+		}
+
+		if self.environment.offline {
+			return resolve_local().ok_or_else(|| anyhow!(OfflineNotCached(url.to_string())));
+		}
+		self.check_host(url, source)?;
+
+		let spinner = self
+			.ui
+			.create_spinner(format!("Fetching repository... [{}]", url));
+		let result = Git::fetch(repo, source);
+		spinner.finish_and_clear();
+		result?;
+
+		resolve_local()
+			.or_else(|| match is_hash {
+				true => Git::fetch_commit(repo, source, rev),
+				false => None,
+			})
+			.ok_or_else(|| {
+				anyhow!(GitRevisionNotFound {
+					url: url.to_string(),
+					rev: rev.to_string(),
+				})
+			})
+	}
+
+	/*
+	 * "../lib.git" in "https://example.com/org/app.git" is
+	 * "https://example.com/org/lib.git", the way git resolves them.
+	 */
+	fn submodule_url(
+		parent: &str,
+		url: &str,
+	) -> String {
+		if !url.starts_with("./") && !url.starts_with("../") {
+			return url.to_string();
+		}
+
+		let mut base = parent.trim_end_matches('/');
+		let mut rest = url;
+		loop {
+			if let Some(stripped) = rest.strip_prefix("./") {
+				rest = stripped;
+			} else if let Some(stripped) = rest.strip_prefix("../") {
+				rest = stripped;
+				base = match base.rfind(['/', ':']) {
+					// "git@example.com:app.git" keeps its colon.
+					Some(index) if base[index..].starts_with(':') => &base[..=index],
+					Some(index) => &base[..index],
+					None => "",
+				};
+			} else {
+				break;
+			}
+		}
+
+		match base.is_empty() || base.ends_with(':') {
+			true => format!("{base}{rest}"),
+			false => format!("{base}/{rest}"),
+		}
+	}
+
+	/*
+	 * Submodule URLs come from the repository rather than the script, they
+	 * go through the same mirrors, host checks and offline mode before git
+	 * gets to fetch them. Nested ones are handled a level at a time.
+	 */
+	fn update_submodules(
+		&mut self,
+		path: &Path,
+		url: &str,
+	) -> anyhow::Result<()> {
+		if !path.join(".gitmodules").exists() {
+			return Ok(());
+		}
+
+		for (name, submodule_path, submodule_url) in Git::submodules(path)? {
+			let url = Self::submodule_url(url, &submodule_url);
+			let source = self.git_source(&url)?;
+			if let Some(argument) = [&url, &source, &submodule_path]
+				.into_iter()
+				.find(|arg| arg.starts_with('-'))
+			{
+				return Err(anyhow!(InvalidGitArgument(argument.clone())));
+			}
+			if self.environment.offline {
+				return Err(anyhow!(OfflineNotCached(url)));
+			}
+			self.check_host(&url, &source)?;
+
+			// Git only copies the URL from .gitmodules when there's none yet.
+			Git::run(path, &["config", &format!("submodule.{name}.url"), &source])?;
+			Git::run(
+				path,
+				&["submodule", "update", "--init", "--quiet", "--", &submodule_path],
+			)?;
+
+			self.update_submodules(&path.join(&submodule_path), &url)?;
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * Checks out a repository at an exact commit and returns the path.
+	 * The commit a branch or tag resolved to is recorded, later runs keep
+	 * using it instead of following the branch.
+	 */
+	pub fn download_git(
+		&mut self,
+		url: String,
+		options: DownloadOptions,
+	) -> anyhow::Result<String> {
+		let rev = options.rev.clone().unwrap_or("HEAD".to_string());
+		let source = self.git_source(&url)?;
+		// Git would take them for options.
+		if let Some(argument) = [&url, &source, &rev].into_iter().find(|arg| arg.starts_with('-')) {
+			return Err(anyhow!(InvalidGitArgument(argument.clone())));
+		}
+		let repo = self.cache.get_dir(&format!("git:{}", url))?;
+
+		let name = options.name.clone().unwrap_or(Lockfile::default_name(&url));
+		let refresh = self.lockfile.should_refresh(&name, &url);
+
+		// numake.lock first, the cache still knows it when there's no lockfile.
+		let rev_key = format!("git:{}#{}", url, rev);
+		let locked = self
+			.lockfile
+			.get(&name, &url, Some(&rev))
+			.and_then(|entry| entry.commit);
+		let recorded = match refresh {
+			true => None,
+			false => locked.or_else(|| {
+				self.cache
+					.get_value(&rev_key)
+					.and_then(|value| value.as_str().map(|str| str.to_string()))
+			}),
+		};
+
+		let commit = match recorded {
+			Some(commit) => commit,
+			None if self.environment.skip_downloads => {
+				return Ok(repo.to_str().unwrap_or("ERROR").to_string())
+			}
+			None => {
+				let commit = self.resolve_git_rev(&url, &source, &repo, &rev)?;
+				self.cache.set_value(&rev_key, toml::Value::from(commit.clone()))?;
+				self.cache.flush()?;
+				commit
+			}
+		};
+
+		let (patches, patches_key) = self.load_patches(&options)?;
+		let checkout_key = format!("git:{}@{}{}", url, commit, patches_key);
+		let done_key = format!("{}#submodules={}", checkout_key, options.submodules);
+		if self.cache.get_value(&done_key).is_some()
+			&& self.cache.check_dir_exists(&checkout_key)
+		{
+			self.cache.flush()?;
+			self.ui.emit(Message::CacheHit {
+				key: checkout_key.clone(),
+			});
+			self.pinned(&name, &url, Some(&rev), Pin::Commit(commit))?;
+			return Ok(self
 				.cache
-				.get_dir(&url)?
+				.get_dir(&checkout_key)?
 				.to_str()
 				.unwrap_or("ERROR")
-				.to_string())
-		} else if self.cache.check_file_exists(&url) {
-  				let spinner = self.ui.create_spinner(format!(
-  					"Archive found in cache. Extracting... [{}]",
-  					&url
-  				));
-  				ZipArchive::new(Cursor::new(self.cache.read_file(&url)?))?
-  					.extract(self.cache.get_dir(&url)?)?;
-  				self.ui.println("Done!", ok::Ok::default());
-  				spinner.finish();
-
-  				Ok(self
-  					.cache
-  					.get_dir(&url)?
-  					.to_str()
-  					.unwrap_or("ERROR")
-  					.to_string())
-  			} else {
-  				let response = reqwest::blocking::get(&url)?;
-  				let status = response.status();
-  				if status.is_success() {
-  					let spinner = self
-  						.ui
-  						.create_spinner("Downloading & extracting archive...");
-  					self.ui.println(
-  						format!(
-  							"Server responded with {}! [{}]",
-  							response.status(),
-  							&url
-  						),
-  						ok::Ok::default(),
-  					);
-  					let path = self.cache.get_dir(&url)?;
-
-  					let data = response.bytes()?;
-  					self.cache.write_file(&url, data.clone())?;
-  					ZipArchive::new(Cursor::new(data.clone()))?
-  						.extract(&path)?;
-  					spinner.finish_and_clear();
-  					self.ui.println(
-  						format!("Done extracting! [{}]", url),
-  						ok::Ok::default(),
-  					);
-
-  					Ok(path.to_str().unwrap().to_string())
-  				} else {
-  					anyhow::bail!("Server responded with {}! [{}]", status, url)
-  				}
-  			}
+				.to_string());
+		}
+
+		if self.environment.skip_downloads {
+			return Ok(self
+				.cache
+				.dir_path(&checkout_key)
+				.to_str()
+				.unwrap_or("ERROR")
+				.to_string());
+		}
+
+		// The recorded commit may have been fetched into a cache since deleted.
+		if Git::resolve(&repo, &commit).is_none() {
+			self.resolve_git_rev(&url, &source, &repo, &commit)?;
+		}
+
+		let spinner = self
+			.ui
+			.create_spinner(format!("Checking out {}... [{}]", commit, url));
+
+		let path = self.cache.get_dir(&checkout_key)?;
+		fs::remove_dir_all(&path)?;
+		fs::create_dir_all(&path)?;
+
+		let repo_str = repo.to_str().unwrap_or("ERROR");
+		let result = Git::run(&path, &["init", "--quiet"])
+			.and_then(|_| Git::run(&path, &["remote", "add", "origin", "--", &source]))
+			.and_then(|_| Git::run(&path, &["fetch", "--quiet", "--no-tags", "--", repo_str, &commit]))
+			.and_then(|_| Git::run(&path, &["checkout", "--quiet", "--detach", &commit]))
+			.and_then(|_| match options.submodules {
+				true => self.update_submodules(&path, &url),
+				false => Ok(()),
+			});
+		spinner.finish_and_clear();
+		result?;
+
+		self.apply_patches(&patches, &path)?;
+
+		self.cache.set_value(&done_key, toml::Value::from(true))?;
+		self.cache.flush()?;
+		self.pinned(&name, &url, Some(&rev), Pin::Commit(commit.clone()))?;
+
+		self.ui.println(
+			format!("Checked out {}! [{}]", commit, url),
+			ok::Ok::default(),
+		);
+
+		Ok(path.to_str().unwrap().to_string())
 	}
 }
 
 impl UserData for Network {
 	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-		methods.add_method_mut("zip", |lua, this, url| unsafe {
-			match this.download_zip(url) {
-				Ok(path) => Ok(path),
-				Err(e) => Err(mlua::Error::RuntimeError(format!("{:?}", e))),
-			}
-		})
+		methods.add_method_mut(
+			"zip",
+			|_, this, (url, options): (String, DownloadOptions)| {
+				this.download_archive(url, options, Some(ArchiveFormat::Zip))
+					.map_err(into_lua_error)
+			},
+		);
+
+		methods.add_method_mut(
+			"archive",
+			|_, this, (url, options): (String, DownloadOptions)| {
+				this.download_archive(url, options, None).map_err(into_lua_error)
+			},
+		);
+
+		methods.add_method_mut(
+			"git",
+			|_, this, (url, options): (String, DownloadOptions)| {
+				this.download_git(url, options).map_err(into_lua_error)
+			},
+		);
+
+		methods.add_method_mut(
+			"download",
+			|_, this, (url, options): (String, DownloadOptions)| {
+				this.download_file(url, options).map_err(into_lua_error)
+			},
+		)
 	}
 }
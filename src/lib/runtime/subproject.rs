@@ -0,0 +1,354 @@
+use crate::lib::compilers::{generic::Generic, mingw::MinGW, msvc::MSVC};
+use crate::lib::data::config::Config;
+use crate::lib::data::environment::Environment;
+use crate::lib::data::project::Project;
+use crate::lib::data::project_language::ProjectLanguage;
+use crate::lib::runtime::{
+	filesystem::Filesystem, modules::Modules, network::Network, storage::Storage,
+	system::System, task_manager::TaskManager,
+};
+use crate::lib::ui::UI;
+use crate::lib::util::build_cache::BuildCache;
+use crate::lib::util::cache::Cache;
+use crate::lib::util::error::NuMakeError::{
+	ReadOnlyGlobal,
+	SubprojectCycle,
+	SubprojectNameTaken,
+	SubprojectNotFound,
+};
+use crate::lib::util::script_cache::ScriptCache;
+use crate::lib::util::{into_lua_error, with_current_dir};
+use anyhow::anyhow;
+use mlua::{AnyUserData, Function, Lua, MultiValue, RegistryKey, Table, Value};
+use pathdiff::diff_paths;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/*
+ * The globals a project script runs with, bound to its directory. The
+ * root script gets one, every `subproject("libs/net")` another one that
+ * shares the task list, lockfile, numake_build directory and download
+ * cache with it. A subproject's tasks are named after its directory,
+ * e.g. `net:build`.
+ */
+#[derive(Clone)]
+pub struct ProjectScope {
+	environment: Environment,
+	tasks: TaskManager,
+	network: Network,
+
+	ui: UI,
+	cache: Cache,
+	build_cache: BuildCache,
+	script_cache: ScriptCache,
+	system: System,
+
+	// Projects created by this script, handed to the parent.
+	projects: Rc<Mutex<Vec<AnyUserData>>>,
+	// Subprojects by name, shared by every scope.
+	loaded: Rc<Mutex<HashMap<String, (PathBuf, RegistryKey)>>>,
+	// Directories of the scripts currently running, outermost first.
+	loading: Rc<Mutex<Vec<PathBuf>>>,
+}
+
+impl ProjectScope {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		environment: Environment,
+		tasks: TaskManager,
+		network: Network,
+		ui: UI,
+		cache: Cache,
+		build_cache: BuildCache,
+		script_cache: ScriptCache,
+		system: System,
+	) -> Self {
+		ProjectScope {
+			loading: Rc::new(Mutex::new(vec![environment.project_directory.clone()])),
+			environment,
+			tasks,
+			network,
+			ui,
+			cache,
+			build_cache,
+			script_cache,
+			system,
+			projects: Rc::new(Mutex::new(Vec::new())),
+			loaded: Rc::new(Mutex::new(HashMap::new())),
+		}
+	}
+
+	/*
+	 * The table the project's script runs in. numake's objects sit in a
+	 * read-only table behind it, everything else comes from Luau's
+	 * sandboxed globals. Scripts can define globals of their own, but
+	 * can't replace `tasks`, `msvc` and the like.
+	 */
+	pub fn globals(
+		&self,
+		lua: &Lua,
+	) -> anyhow::Result<Table> {
+		let globals = lua.create_table()?;
+		let injected = lua.create_table()?;
+		self.push_globals(lua, &injected, &globals)?;
+
+		let metatable = lua.create_table()?;
+		metatable.set("__index", lua.globals())?;
+		injected.set_metatable(Some(metatable));
+		injected.set_readonly(true);
+
+		let metatable = lua.create_table()?;
+		metatable.set("__index", injected.clone())?;
+		metatable.set(
+			"__newindex",
+			lua.create_function(
+				move |_, (globals, key, value): (Table, Value, Value)| {
+					if !injected.raw_get::<Value>(key.clone())?.is_nil() {
+						return Err(mlua::Error::external(ReadOnlyGlobal(
+							key.to_string()?,
+						)));
+					}
+					globals.raw_set(key, value)
+				},
+			)?,
+		)?;
+		globals.set_metatable(Some(metatable));
+
+		Ok(globals)
+	}
+
+	/*
+	 * `globals` is the script's own table, modules required from the
+	 * script run in it too.
+	 */
+	fn push_globals(
+		&self,
+		lua: &Lua,
+		injected: &Table,
+		globals: &Table,
+	) -> anyhow::Result<()> {
+		let projects = self.projects.clone();
+		injected.set(
+			"new_project",
+			lua.create_function(
+				move |lua, (name, language): (String, ProjectLanguage)| {
+					let project = lua.create_userdata(Project {
+						name,
+						language,
+						..Default::default()
+					})?;
+					(*projects.lock().unwrap()).push(project.clone());
+					Ok(project)
+				},
+			)?,
+		)?;
+
+		// stdout is the event stream with --message-format=json.
+		if self.ui.is_json() {
+			injected.set(
+				"print",
+				lua.create_function(|lua, args: MultiValue| {
+					let tostring: Function = lua.globals().get("tostring")?;
+					let parts = args
+						.into_iter()
+						.map(|arg| tostring.call::<String>(arg))
+						.collect::<mlua::Result<Vec<_>>>()?;
+					eprintln!("{}", parts.join("\t"));
+					Ok(())
+				})?,
+			)?;
+		}
+
+		let modules = Modules::new(self.environment.clone(), self.script_cache.clone());
+		injected.set("require", modules.create_require(lua, globals)?)?;
+		injected.set("subproject", self.create_subproject(lua)?)?;
+		injected.set("storage", Storage::new(self.cache.clone()))?;
+		injected.set("filesystem", Filesystem::new(self.environment.clone()))?;
+		injected.set("tasks", self.tasks.clone())?;
+		injected.set("network", self.network.clone())?;
+		injected.set(
+			"msvc",
+			MSVC::new(
+				self.environment.clone(),
+				self.build_cache.clone(),
+				self.ui.clone(),
+				self.system.clone(),
+			),
+		)?;
+		injected.set(
+			"mingw",
+			MinGW::new(
+				self.environment.clone(),
+				self.build_cache.clone(),
+				self.ui.clone(),
+				self.system.clone(),
+			),
+		)?;
+		injected.set(
+			"generic",
+			Generic::new(
+				self.environment.clone(),
+				self.build_cache.clone(),
+				self.ui.clone(),
+				self.system.clone(),
+			),
+		)?;
+
+		Ok(())
+	}
+
+	/*
+	 * Runs the script in `path` and returns what it defined:
+	 *
+	 *   { name = "net", directory = "...", projects = { [name] = project }, tasks = { "net:build" } }
+	 *
+	 * Loading the same directory again returns the same table.
+	 */
+	fn subproject(
+		&self,
+		lua: &Lua,
+		path: &str,
+		file: Option<&str>,
+	) -> anyhow::Result<Table> {
+		let directory = dunce::canonicalize(self.environment.project_directory.join(path))
+			.map_err(|_| anyhow!(SubprojectNotFound(path.to_string())))?;
+		self.environment.path_policy.readable(&directory)?;
+		let project_file = directory.join(file.unwrap_or("project.lua"));
+		if !project_file.is_file() {
+			return Err(anyhow!(SubprojectNotFound(project_file.display().to_string())));
+		}
+
+		let name = directory
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.ok_or_else(|| anyhow!(SubprojectNotFound(path.to_string())))?;
+		let namespace = self.tasks.namespace(&name);
+
+		match (*self.loaded.lock().unwrap()).get(&namespace) {
+			Some((loaded, key)) if *loaded == directory => return Ok(lua.registry_value(key)?),
+			Some((loaded, _)) => {
+				return Err(anyhow!(SubprojectNameTaken {
+					name: namespace,
+					first: loaded.display().to_string(),
+					second: directory.display().to_string(),
+				}))
+			}
+			None => {}
+		}
+
+		{
+			let mut loading = self.loading.lock().unwrap();
+			if let Some(start) = loading.iter().position(|loading| *loading == directory) {
+				let chain: Vec<String> = loading[start..]
+					.iter()
+					.chain(std::iter::once(&directory))
+					.map(|directory| directory.display().to_string())
+					.collect();
+				return Err(anyhow!(SubprojectCycle(chain.join(" -> "))));
+			}
+			loading.push(directory.clone());
+		}
+
+		let result = self.run(lua, &namespace, &name, directory.clone(), project_file);
+		(*self.loading.lock().unwrap()).pop();
+		let result = result?;
+
+		(*self.loaded.lock().unwrap())
+			.insert(namespace, (directory, lua.create_registry_value(result.clone())?));
+
+		Ok(result)
+	}
+
+	fn run(
+		&self,
+		lua: &Lua,
+		namespace: &str,
+		name: &str,
+		directory: PathBuf,
+		project_file: PathBuf,
+	) -> anyhow::Result<Table> {
+		// A numake.toml of its own replaces the parent's, NUMAKE_CONFIG is
+		// only for the root project.
+		let config_file = directory.join("numake.toml");
+		let (config, path_policy) = match config_file.exists() {
+			true => {
+				let config = Config::load_file(&config_file)?;
+				let path_policy = self.environment.path_policy.with_config(&directory, &config);
+				(config, path_policy)
+			}
+			false => (
+				self.environment.config.clone(),
+				self.environment.path_policy.clone(),
+			),
+		};
+		let environment = Environment {
+			project_directory: directory.clone(),
+			project_file: project_file.clone(),
+			config,
+			path_policy,
+			..self.environment.clone()
+		};
+
+		let scope = ProjectScope {
+			network: self.network.for_environment(environment.clone()),
+			tasks: self.tasks.scoped(name, &directory),
+			environment,
+			projects: Rc::new(Mutex::new(Vec::new())),
+			..self.clone()
+		};
+
+		let globals = scope.globals(lua)?;
+
+		let chunk_name = diff_paths(&project_file, &self.environment.root_directory)
+			.unwrap_or(project_file.clone());
+		let bytecode = self
+			.script_cache
+			.clone()
+			.compile(&chunk_name.display().to_string(), &fs::read(&project_file)?)?;
+
+		// Project's methods check paths against the policy Lua holds.
+		let parent_policy = lua.set_app_data(scope.environment.path_policy.clone());
+		let result = with_current_dir(&directory, || {
+			Ok(lua
+				.load(bytecode)
+				.set_name(format!("@{}", chunk_name.display()))
+				.set_environment(globals)
+				.exec()?)
+		});
+		if let Some(policy) = parent_policy {
+			lua.set_app_data(policy);
+		}
+		result?;
+
+		let projects = lua.create_table()?;
+		for project in (*scope.projects.lock().unwrap()).iter() {
+			projects.set(project.borrow::<Project>()?.name.clone(), project.clone())?;
+		}
+
+		let result = lua.create_table()?;
+		result.set("name", namespace)?;
+		result.set("directory", directory.display().to_string())?;
+		result.set("projects", projects)?;
+		result.set("tasks", scope.tasks.get_own_tasks())?;
+
+		Ok(result)
+	}
+
+	/*
+	 * The global `subproject` function.
+	 */
+	fn create_subproject(
+		&self,
+		lua: &Lua,
+	) -> mlua::Result<Function> {
+		let scope = self.clone();
+		lua.create_function(move |lua, (path, file): (String, Option<String>)| {
+			scope
+				.subproject(lua, &path, file.as_deref())
+				.map_err(into_lua_error)
+		})
+	}
+}
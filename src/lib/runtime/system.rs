@@ -1,32 +1,250 @@
 use crate::lib::ui::format::info::Info;
-use crate::lib::ui::format::{error, ok};
+use crate::lib::ui::format::{default, error, ok};
+use crate::lib::ui::message::Message;
 use crate::lib::ui::UI;
+use crate::lib::util::diagnostics::{Diagnostic, Diagnostics};
+use crate::lib::util::timings::{SpanGuard, Timings};
+use crate::lib::util::error::NuMakeError::{CommandFailed, CompilationFailed, Interrupted};
 use anyhow::anyhow;
-use std::process::{Command, ExitStatus};
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use std::{process, thread};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
 #[derive(Clone)]
 pub struct System {
 	ui: UI,
+	diagnostics: Diagnostics,
+	timings: Timings,
 }
 
 impl System {
-	pub fn new(ui: UI) -> System {
-		System { ui }
+	pub fn new(
+		ui: UI,
+		diagnostics: Diagnostics,
+		timings: Timings,
+	) -> System {
+		System {
+			ui,
+			diagnostics,
+			timings,
+		}
+	}
+
+	pub fn span(
+		&self,
+		category: &str,
+		name: impl Into<String>,
+	) -> SpanGuard {
+		self.timings.span(category, name)
+	}
+
+	/*
+	 * The first Ctrl-C only raises a flag so running children can be
+	 * killed and the build cache saved. A second one exits immediately.
+	 */
+	pub fn handle_interrupts(ui: &UI) -> anyhow::Result<()> {
+		let ui = ui.clone();
+		ctrlc::set_handler(move || {
+			if INTERRUPTED.swap(true, Ordering::SeqCst) {
+				process::exit(130);
+			}
+
+			ui.println(
+				"Interrupted! Stopping build, press Ctrl-C again to force quit.",
+				Info::default(),
+			);
+		})?;
+
+		Ok(())
+	}
+
+	pub fn interrupted() -> bool {
+		INTERRUPTED.load(Ordering::SeqCst)
+	}
+
+	pub fn check_interrupted() -> anyhow::Result<()> {
+		if Self::interrupted() {
+			Err(anyhow!(Interrupted))
+		} else {
+			Ok(())
+		}
+	}
+
+	/*
+	 * Like Command::output(), but every line the child writes is printed
+	 * as soon as it arrives, and the child is killed as soon as the user
+	 * interrupts the build. Lines are printed by this thread only, so the
+	 * output of one command is never interleaved with anything else.
+	 */
+	fn wait_for_output(
+		&self,
+		cmd: &mut Command,
+	) -> anyhow::Result<Output> {
+		Self::check_interrupted()?;
+
+		let argv: Vec<String> = std::iter::once(cmd.get_program())
+			.chain(cmd.get_args())
+			.map(|arg| arg.to_string_lossy().to_string())
+			.collect();
+
+		let mut child = cmd
+			.stdin(Stdio::null())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()?;
+		self.ui.emit(Message::CommandStarted { argv: argv.clone() });
+		let start = Instant::now();
+
+		let (sender, receiver) = mpsc::channel::<(bool, Vec<u8>)>();
+		Self::read_pipe(child.stdout.take(), false, sender.clone());
+		Self::read_pipe(child.stderr.take(), true, sender);
+
+		let mut stdout: Vec<u8> = Vec::new();
+		let mut stderr: Vec<u8> = Vec::new();
+		let mut status: Option<ExitStatus> = None;
+
+		loop {
+			match receiver.recv_timeout(Duration::from_millis(10)) {
+				Ok((is_stderr, line)) => {
+					self.ui.println(
+						String::from_utf8_lossy(&line).trim_end(),
+						default::Default::default(),
+					);
+
+					let buf = if is_stderr { &mut stderr } else { &mut stdout };
+					buf.extend(line);
+					buf.push(b'\n');
+				}
+
+				// Both pipes are closed, nothing left to print.
+				Err(RecvTimeoutError::Disconnected) => {
+					if status.is_some() {
+						break;
+					}
+					thread::sleep(Duration::from_millis(10));
+				}
+
+				Err(RecvTimeoutError::Timeout) => {}
+			}
+
+			if status.is_none() {
+				status = child.try_wait()?;
+			}
+
+			if status.is_none() && Self::interrupted() {
+				let _ = child.kill();
+				let _ = child.wait();
+				self.ui.emit(Message::CommandFinished {
+					argv,
+					success: false,
+					exit_code: None,
+					duration_ms: start.elapsed().as_millis() as u64,
+				});
+				return Err(anyhow!(Interrupted));
+			}
+		}
+
+		let status = status.unwrap();
+		self.ui.emit(Message::CommandFinished {
+			argv,
+			success: status.success(),
+			exit_code: status.code(),
+			duration_ms: start.elapsed().as_millis() as u64,
+		});
+
+		Ok(Output {
+			status,
+			stdout,
+			stderr,
+		})
+	}
+
+	fn read_pipe<R: Read + Send + 'static>(
+		pipe: Option<R>,
+		is_stderr: bool,
+		sender: Sender<(bool, Vec<u8>)>,
+	) {
+		thread::spawn(move || {
+			if let Some(pipe) = pipe {
+				for line in BufReader::new(pipe).split(b'\n').map_while(Result::ok) {
+					if sender.send((is_stderr, line)).is_err() {
+						break;
+					}
+				}
+			}
+		});
+	}
+
+	/*
+	 * Turns the failures collected during a compilation step into a
+	 * single result. A lone failure is passed through untouched, several
+	 * (--keep-going) are printed one by one and summarized.
+	 */
+	pub(crate) fn collect_failures(
+		&self,
+		mut failures: Vec<(PathBuf, anyhow::Error)>,
+	) -> anyhow::Result<()> {
+		Self::check_interrupted()?;
+
+		match failures.len() {
+			0 => Ok(()),
+
+			1 => Err(failures.remove(0).1),
+
+			count => {
+				for (file, err) in failures {
+					self.ui.println(
+						format!("{}: {:#}", file.display(), err),
+						error::Error::default(),
+					);
+				}
+
+				Err(anyhow!(CompilationFailed(count)))
+			}
+		}
+	}
+
+	fn emit_diagnostics(
+		&self,
+		diagnostics: Vec<Diagnostic>,
+	) {
+		for diagnostic in diagnostics {
+			self.ui.emit(Message::Diagnostic(diagnostic));
+		}
+	}
+
+	/*
+	 * The output was printed while the command ran, the error only says
+	 * which command failed so it isn't printed a second time.
+	 */
+	fn command_failed(
+		cmd: &Command,
+		status: ExitStatus,
+	) -> anyhow::Error {
+		anyhow!(CommandFailed {
+			program: cmd.get_program().to_string_lossy().to_string(),
+			status: status.to_string(),
+		})
 	}
 
 	pub(crate) fn execute(
 		&self,
 		cmd: &mut Command,
 	) -> anyhow::Result<ExitStatus> {
-		let output = cmd.output()?;
+		let output = self.wait_for_output(cmd)?;
 		let stderr =
 			String::from_utf8_lossy(&output.stderr).to_string();
+		self.emit_diagnostics(
+			self.diagnostics.collect(&stderr, Diagnostic::parse_gcc),
+		);
 
 		if output.status.success() {
-			if !stderr.is_empty() {
-				self.ui.println(stderr.clone() + "\n", Info::default());
-			}
-
 			self.ui.println(
 				format!(
 					"{} exited with {}",
@@ -37,25 +255,20 @@ impl System {
 			);
 			Ok(output.status)
 		} else {
-			self.ui.println(
-				format!(
-					"{} exited with {}",
-					cmd.get_program().to_str().unwrap(),
-					output.status
-				),
-				error::Error::default(),
-			);
-			Err(anyhow!(stderr))
+			Err(Self::command_failed(cmd, output.status))
 		}
 	}
 
+	#[cfg_attr(not(windows), allow(dead_code))]
 	pub(crate) fn msvc_execute(
 		&self,
 		cmd: &mut Command,
 	) -> anyhow::Result<ExitStatus> {
-		let result = cmd.output();
+		let result = self.wait_for_output(cmd);
 
 		match result {
+			Err(err) if Self::interrupted() => Err(err),
+
 			Err(err) => Err(anyhow!(format!(
 				"Error trying to execute {}! {}",
 				cmd.get_program().to_str().unwrap(),
@@ -65,14 +278,11 @@ impl System {
 			Ok(output) => {
 				let stdout =
 					String::from_utf8_lossy(&output.stdout).to_string();
+				self.emit_diagnostics(
+					self.diagnostics.collect(&stdout, Diagnostic::parse_msvc),
+				);
 
 				if output.status.success() {
-					self.ui.println(if stdout.contains(": warning ") {
-						stdout.clone()
-					} else {
-						stdout.clone()
-					} + "\n", Info::default());
-
 					self.ui.println(format!(
 						"{} exited with {}",
 						cmd.get_program().to_str().unwrap(),
@@ -80,12 +290,7 @@ impl System {
 					), ok::Ok::default());
 					Ok(output.status)
 				} else {
-					self.ui.println(format!(
-						"{} exited with {}",
-						cmd.get_program().to_str().unwrap(),
-						output.status
-					), error::Error::default());
-					Err(anyhow!(stdout))
+					Err(Self::command_failed(cmd, output.status))
 				}
 			}
 		}
@@ -1,39 +1,97 @@
 use crate::lib::util::error::NuMakeError::TaskNotFound;
+use crate::lib::util::script_error::ScriptError;
+use crate::lib::util::with_current_dir;
 use anyhow::anyhow;
-use mlua::prelude::{LuaFunction, LuaResult, LuaValue};
+use mlua::prelude::{LuaFunction, LuaValue};
 use mlua::{FromLua, Lua, UserData, UserDataMethods, Value};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use std::sync::Mutex;
 
+#[derive(Clone)]
+struct Task {
+	function: LuaFunction,
+	// Tasks run in the directory of the project that created them.
+	directory: PathBuf,
+}
+
+/*
+ * Every project shares one task list. A subproject's tasks are prefixed
+ * with its name, `build` in libs/net becomes `net:build`.
+ */
 #[derive(Clone)]
 pub struct TaskManager {
-	tasks: Rc<Mutex<HashMap<String, LuaFunction>>>,
+	tasks: Rc<Mutex<HashMap<String, Task>>>,
+	prefix: String,
+	directory: PathBuf,
 }
 
 impl TaskManager {
-	pub fn new() -> Self {
+	pub fn new(directory: PathBuf) -> Self {
 		TaskManager {
 			tasks: Rc::new(Mutex::new(HashMap::new())),
+			prefix: String::new(),
+			directory,
+		}
+	}
+
+	/*
+	 * The task list as seen from a subproject.
+	 */
+	pub fn scoped(
+		&self,
+		name: &str,
+		directory: &Path,
+	) -> Self {
+		TaskManager {
+			tasks: self.tasks.clone(),
+			prefix: self.namespace(name) + ":",
+			directory: directory.to_path_buf(),
 		}
 	}
 
+	/*
+	 * A subproject's full name, e.g. `net:http` for libs/http inside libs/net.
+	 */
+	pub fn namespace(
+		&self,
+		name: &str,
+	) -> String {
+		self.prefix.clone() + name
+	}
+
 	pub fn run(
 		&self,
-		task: &str,
+		task_name: &str,
 	) -> anyhow::Result<()> {
-		match (*self.tasks.lock().unwrap()).get(task) {
-			Some(t) => match t.call::<()>(()) {
-				Ok(_) => Ok(()),
-				Err(e) => Err(anyhow!(e)),
-			},
+		// Not locked while running, tasks may create other tasks.
+		let task = (*self.tasks.lock().unwrap()).get(task_name).cloned();
+
+		match task {
+			Some(t) => {
+				with_current_dir(&t.directory, || Ok(t.function.call::<()>(())))?
+					.map_err(|e| anyhow!(ScriptError::new(e, Some(task_name))))
+			}
 
 			None => Err(anyhow!(TaskNotFound)),
 		}
 	}
 
 	pub fn get_tasks(&self) -> Vec<String> {
-		(*self.tasks.lock().unwrap()).keys().cloned().collect()
+		let mut tasks: Vec<String> = (*self.tasks.lock().unwrap()).keys().cloned().collect();
+		tasks.sort();
+		tasks
+	}
+
+	/*
+	 * Tasks created by this project and its subprojects.
+	 */
+	pub fn get_own_tasks(&self) -> Vec<String> {
+		self.get_tasks()
+			.into_iter()
+			.filter(|task| task.starts_with(&self.prefix))
+			.collect()
 	}
 }
 
@@ -42,7 +100,13 @@ impl UserData for TaskManager {
 		methods.add_method_mut(
 			"create",
 			|_, this, (name, task): (String, LuaFunction)| {
-				(*this.tasks.lock().unwrap()).insert(name, task);
+				(*this.tasks.lock().unwrap()).insert(
+					this.prefix.clone() + &name,
+					Task {
+						function: task,
+						directory: this.directory.clone(),
+					},
+				);
 				Ok(())
 			},
 		);
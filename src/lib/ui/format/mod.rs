@@ -2,6 +2,7 @@ pub mod default;
 pub mod error;
 pub mod ok;
 pub mod info;
+pub mod warning;
 
 pub trait Format {
     fn format<I: AsRef<str>>(&self, input: I) -> String;
@@ -0,0 +1,13 @@
+use crate::lib::ui::format::Format;
+use console::style;
+
+#[derive(Default)]
+pub struct Warning {}
+impl Format for Warning {
+	fn format<I: AsRef<str>>(
+		&self,
+		input: I,
+	) -> String {
+		format!("{} {}", style("warning:").yellow().bold().bright(), input.as_ref())
+	}
+}
@@ -0,0 +1,76 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::lib::util::diagnostics::Diagnostic;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MessageFormat {
+	/// Coloured output and progress bars.
+	#[default]
+	Human,
+	/// Newline-delimited JSON events on stdout.
+	Json,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Message {
+	TaskStarted {
+		task: String,
+	},
+
+	TaskFinished {
+		task: String,
+		success: bool,
+		duration_ms: u64,
+	},
+
+	CommandStarted {
+		argv: Vec<String>,
+	},
+
+	CommandFinished {
+		argv: Vec<String>,
+		success: bool,
+		exit_code: Option<i32>,
+		duration_ms: u64,
+	},
+
+	Diagnostic(Diagnostic),
+
+	Artifact {
+		project: String,
+		path: String,
+	},
+
+	CacheHit {
+		key: String,
+	},
+
+	// Results of `numake list`, `numake fetch` and `numake cache list`.
+	Tasks {
+		tasks: Vec<String>,
+	},
+
+	Dependency {
+		url: String,
+		pin: String,
+	},
+
+	CacheEntry {
+		key: String,
+		size: u64,
+		last_used: u64,
+		shared: bool,
+	},
+
+	CacheSummary {
+		size: u64,
+		entries: usize,
+		store: String,
+	},
+
+	Error {
+		message: String,
+	},
+}
@@ -1,21 +1,32 @@
 pub mod format;
+pub mod message;
 
 use crate::lib::ui::format::Format;
-use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use crate::lib::ui::message::{Message, MessageFormat};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use std::borrow::Cow;
+use std::fmt::Display;
+use std::io::{self, Write};
 
 #[derive(Clone)]
 pub struct UI {
 	bar_manager: MultiProgress,
+	message_format: MessageFormat,
 }
 
 impl UI {
-	pub fn new(quiet: bool) -> UI {
+	pub fn new(
+		quiet: bool,
+		message_format: MessageFormat,
+	) -> UI {
 		UI {
-			bar_manager: MultiProgress::with_draw_target(match quiet {
-				true => ProgressDrawTarget::hidden(),
-				false => ProgressDrawTarget::stderr(),
-			}),
+			bar_manager: MultiProgress::with_draw_target(
+				match quiet || message_format == MessageFormat::Json {
+					true => ProgressDrawTarget::hidden(),
+					false => ProgressDrawTarget::stderr(),
+				},
+			),
+			message_format,
 		}
 	}
 
@@ -36,6 +47,32 @@ impl UI {
 			.add(ProgressBar::new_spinner().with_message(message))
 	}
 
+	/*
+	 * Counts bytes, without a length (no Content-Length) it's a spinner.
+	 */
+	pub fn create_download_bar(
+		&self,
+		length: Option<u64>,
+		message: impl Into<Cow<'static, str>>,
+	) -> ProgressBar {
+		let (bar, template) = match length {
+			Some(length) => (
+				ProgressBar::new(length),
+				"{msg} [{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} {eta}",
+			),
+			None => (
+				ProgressBar::new_spinner(),
+				"{spinner} {msg} {bytes} {bytes_per_sec}",
+			),
+		};
+
+		if let Ok(style) = ProgressStyle::with_template(template) {
+			bar.set_style(style.progress_chars("=> "));
+		}
+
+		self.bar_manager.add(bar.with_message(message))
+	}
+
 	pub fn remove_bar(
 		&mut self,
 		bar: ProgressBar,
@@ -48,9 +85,43 @@ impl UI {
 		msg: I,
 		formatter: impl Format,
 	) {
-		match self.bar_manager.println(formatter.format(msg)) {
-			Ok(_) => (),
-			Err(_) => (),
+		let _ = self.bar_manager.println(formatter.format(msg));
+	}
+
+	/*
+	 * Machine-readable counterpart of println, only does something
+	 * when running with --message-format=json.
+	 */
+	pub fn emit(
+		&self,
+		message: Message,
+	) {
+		if self.message_format == MessageFormat::Json {
+			if let Ok(json) = serde_json::to_string(&message) {
+				let _ = writeln!(io::stdout(), "{json}");
+			}
+		}
+	}
+
+	pub fn is_json(&self) -> bool {
+		self.message_format == MessageFormat::Json
+	}
+
+	/*
+	 * What a command prints as its result, like the task list. Plain text
+	 * on stdout, or the message with --message-format=json so the event
+	 * stream stays valid.
+	 */
+	pub fn output(
+		&self,
+		text: impl Display,
+		message: Message,
+	) {
+		match self.is_json() {
+			true => self.emit(message),
+			false => {
+				let _ = writeln!(io::stdout(), "{text}");
+			}
 		}
 	}
 }
@@ -0,0 +1,452 @@
+use crate::lib::data::config::ArchiveConfig;
+use crate::lib::util::error::NuMakeError::{
+	ArchiveEntryOutsideDestination,
+	ArchiveTooLarge,
+	ArchiveTooManyEntries,
+};
+use anyhow::anyhow;
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use liblzma::read::XzDecoder;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use zip::ZipArchive;
+
+const MAGIC: [(&[u8], ArchiveFormat); 6] = [
+	(b"PK\x03\x04", ArchiveFormat::Zip),
+	(b"PK\x05\x06", ArchiveFormat::Zip),
+	(b"\x1f\x8b", ArchiveFormat::TarGz),
+	(b"\xfd7zXZ\x00", ArchiveFormat::TarXz),
+	(b"BZh", ArchiveFormat::TarBz2),
+	(b"\x28\xb5\x2f\xfd", ArchiveFormat::TarZst),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+	Zip,
+	Tar,
+	TarGz,
+	TarXz,
+	TarBz2,
+	TarZst,
+}
+
+impl ArchiveFormat {
+	/*
+	 * Magic bytes first, release servers don't always keep the extension
+	 * (e.g. GitHub's /archive/<rev> URLs). The extension is the fallback
+	 * for plain tarballs, whose magic sits at an offset.
+	 */
+	pub fn detect(
+		url: &str,
+		data: &[u8],
+	) -> Option<ArchiveFormat> {
+		if let Some((_, format)) =
+			MAGIC.iter().find(|(magic, _)| data.starts_with(magic))
+		{
+			return Some(*format);
+		}
+
+		if data.get(257..262) == Some(b"ustar") {
+			return Some(ArchiveFormat::Tar);
+		}
+
+		let path = url
+			.split(['?', '#'])
+			.next()
+			.unwrap_or_default()
+			.to_lowercase();
+
+		[
+			(".zip", ArchiveFormat::Zip),
+			(".tar.gz", ArchiveFormat::TarGz),
+			(".tgz", ArchiveFormat::TarGz),
+			(".tar.xz", ArchiveFormat::TarXz),
+			(".txz", ArchiveFormat::TarXz),
+			(".tar.bz2", ArchiveFormat::TarBz2),
+			(".tbz2", ArchiveFormat::TarBz2),
+			(".tar.zst", ArchiveFormat::TarZst),
+			(".tzst", ArchiveFormat::TarZst),
+			(".tar", ArchiveFormat::Tar),
+		]
+		.into_iter()
+		.find(|(extension, _)| path.ends_with(extension))
+		.map(|(_, format)| format)
+	}
+
+	/*
+	 * Whether the data starts like something compressed, zip included.
+	 */
+	pub fn is_compressed(data: &[u8]) -> bool {
+		MAGIC.iter().any(|(magic, _)| data.starts_with(magic))
+	}
+}
+
+/*
+ * Extracts an archive, optionally dropping leading path components
+ * (like tar's --strip-components) and keeping only one subdirectory.
+ *
+ * Nothing is ever written outside the destination: absolute paths, ".."
+ * components and symlinks pointing out of it are rejected, as are
+ * archives exceeding the configured size and entry count.
+ */
+pub struct Extractor {
+	pub url: String,
+	pub strip_components: usize,
+	pub subdir: Option<PathBuf>,
+	pub limits: ArchiveConfig,
+}
+
+struct Extraction {
+	destination: PathBuf,
+	entries: usize,
+	extracted: usize,
+	size: u64,
+	symlinks: Vec<PathBuf>,
+}
+
+impl Extractor {
+	fn outside(entry: &Path) -> anyhow::Error {
+		anyhow!(ArchiveEntryOutsideDestination {
+			entry: entry.to_string_lossy().to_string(),
+		})
+	}
+
+	/*
+	 * Where an entry ends up relative to the destination, None if it's
+	 * filtered out.
+	 */
+	fn target_path(
+		&self,
+		name: &Path,
+	) -> anyhow::Result<Option<PathBuf>> {
+		let mut path = PathBuf::new();
+		for component in name.components() {
+			match component {
+				Component::Normal(part) => path.push(part),
+				Component::CurDir => {}
+				_ => return Err(Self::outside(name)),
+			}
+		}
+
+		let path: PathBuf =
+			path.components().skip(self.strip_components).collect();
+
+		let path = match &self.subdir {
+			Some(subdir) => match path.strip_prefix(subdir) {
+				Ok(path) => path.to_path_buf(),
+				Err(_) => return Ok(None),
+			},
+			None => path,
+		};
+
+		if path.as_os_str().is_empty() {
+			Ok(None)
+		} else {
+			Ok(Some(path))
+		}
+	}
+
+	/*
+	 * Makes sure the entry's parent directory, after resolving symlinks
+	 * extracted earlier, is still inside the destination. Returns the
+	 * resolved parent.
+	 */
+	fn prepare(
+		&self,
+		extraction: &Extraction,
+		entry: &Path,
+		path: &Path,
+	) -> anyhow::Result<PathBuf> {
+		let parent = path.parent().unwrap_or(&extraction.destination);
+
+		// Never create directories through a symlink before checking it.
+		let mut existing = parent;
+		while !existing.exists() {
+			existing = existing.parent().unwrap_or(&extraction.destination);
+		}
+		if !dunce::canonicalize(existing)?.starts_with(&extraction.destination) {
+			return Err(Self::outside(entry));
+		}
+
+		fs::create_dir_all(parent)?;
+		let parent = dunce::canonicalize(parent)?;
+		if !parent.starts_with(&extraction.destination) {
+			return Err(Self::outside(entry));
+		}
+
+		// Replace rather than write through an earlier entry's symlink or
+		// hard link.
+		if path.symlink_metadata().is_ok_and(|meta| !meta.is_dir()) {
+			fs::remove_file(path)?;
+		}
+
+		Ok(parent)
+	}
+
+	fn count_entry(
+		&self,
+		extraction: &mut Extraction,
+	) -> anyhow::Result<()> {
+		extraction.entries += 1;
+		if extraction.entries > self.limits.max_entries {
+			return Err(anyhow!(ArchiveTooManyEntries {
+				url: self.url.clone(),
+				limit: self.limits.max_entries,
+			}));
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * Copies at most the remaining size budget, declared sizes in archive
+	 * headers can't be trusted.
+	 */
+	fn write_file<R: Read>(
+		&self,
+		extraction: &mut Extraction,
+		reader: &mut R,
+		path: &Path,
+	) -> anyhow::Result<()> {
+		let remaining = self.limits.max_size.0.saturating_sub(extraction.size);
+		let written = io::copy(
+			&mut reader.take(remaining + 1),
+			&mut File::create(path)?,
+		)?;
+
+		extraction.size += written;
+		if written > remaining {
+			return Err(anyhow!(ArchiveTooLarge {
+				url: self.url.clone(),
+				limit: self.limits.max_size,
+			}));
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * `target` applied to `base` as written, None for absolute targets.
+	 */
+	fn resolve_lexically(
+		base: &Path,
+		target: &Path,
+	) -> Option<PathBuf> {
+		let mut resolved = base.to_path_buf();
+		for component in target.components() {
+			match component {
+				Component::Normal(part) => resolved.push(part),
+				Component::CurDir => {}
+				Component::ParentDir => {
+					resolved.pop();
+				}
+				_ => return None,
+			}
+		}
+
+		Some(resolved)
+	}
+
+	/*
+	 * Symlink targets are relative to the link's directory, resolve them
+	 * lexically since they may not exist yet. `parent` is the resolved
+	 * directory, the link's path itself may lead through other links.
+	 */
+	fn check_symlink(
+		&self,
+		extraction: &Extraction,
+		entry: &Path,
+		parent: &Path,
+		target: &Path,
+	) -> anyhow::Result<()> {
+		match Self::resolve_lexically(parent, target) {
+			Some(resolved) if resolved.starts_with(&extraction.destination) => Ok(()),
+			_ => Err(Self::outside(entry)),
+		}
+	}
+
+	/*
+	 * Returns how many entries were extracted. Tarballs are streamed, zip
+	 * needs the file to seek to its central directory.
+	 */
+	pub fn extract(
+		&self,
+		file: File,
+		format: ArchiveFormat,
+		destination: &Path,
+	) -> anyhow::Result<usize> {
+		let mut extraction = Extraction {
+			destination: dunce::canonicalize(destination)?,
+			entries: 0,
+			extracted: 0,
+			size: 0,
+			symlinks: Vec::new(),
+		};
+
+		let reader = BufReader::new(file);
+		match format {
+			ArchiveFormat::Zip => self.extract_zip(reader, &mut extraction),
+			ArchiveFormat::Tar => self.extract_tar(reader, &mut extraction),
+			ArchiveFormat::TarGz => {
+				self.extract_tar(GzDecoder::new(reader), &mut extraction)
+			}
+			ArchiveFormat::TarXz => {
+				self.extract_tar(XzDecoder::new(reader), &mut extraction)
+			}
+			ArchiveFormat::TarBz2 => {
+				self.extract_tar(BzDecoder::new(reader), &mut extraction)
+			}
+			ArchiveFormat::TarZst => self.extract_tar(
+				zstd::stream::read::Decoder::new(reader)?,
+				&mut extraction,
+			),
+		}?;
+
+		/*
+		 * Each symlink was checked on its own, but a chain of them can
+		 * still lead outside once everything is in place.
+		 */
+		for link in &extraction.symlinks {
+			let resolved = dunce::canonicalize(link).ok().or_else(|| {
+				// Dangling, there's only the target as written to go by.
+				let parent = dunce::canonicalize(link.parent()?).ok()?;
+				Self::resolve_lexically(&parent, &fs::read_link(link).ok()?)
+			});
+
+			if !resolved.is_some_and(|resolved| resolved.starts_with(&extraction.destination)) {
+				let entry = link
+					.strip_prefix(&extraction.destination)
+					.unwrap_or(link)
+					.to_path_buf();
+				return Err(Self::outside(&entry));
+			}
+		}
+
+		Ok(extraction.extracted)
+	}
+
+	fn extract_zip(
+		&self,
+		reader: BufReader<File>,
+		extraction: &mut Extraction,
+	) -> anyhow::Result<()> {
+		let mut archive = ZipArchive::new(reader)?;
+
+		for i in 0..archive.len() {
+			self.count_entry(extraction)?;
+
+			let mut file = archive.by_index(i)?;
+			let entry = PathBuf::from(file.name());
+			let Some(path) = self.target_path(&entry)? else {
+				continue;
+			};
+			let path = extraction.destination.join(path);
+
+			if file.is_dir() {
+				self.prepare(extraction, &entry, &path.join("."))?;
+				extraction.extracted += 1;
+				continue;
+			}
+
+			let parent = self.prepare(extraction, &entry, &path)?;
+
+			if file.is_symlink() {
+				let mut target = String::new();
+				file.read_to_string(&mut target)?;
+				self.check_symlink(extraction, &entry, &parent, Path::new(&target))?;
+
+				#[cfg(unix)]
+				{
+					std::os::unix::fs::symlink(&target, &path)?;
+					extraction.symlinks.push(path);
+					extraction.extracted += 1;
+					continue;
+				}
+			}
+
+			self.write_file(extraction, &mut file, &path)?;
+
+			#[cfg(unix)]
+			if let Some(mode) = file.unix_mode() {
+				use std::os::unix::fs::PermissionsExt;
+				fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+			}
+
+			extraction.extracted += 1;
+		}
+
+		Ok(())
+	}
+
+	fn extract_tar<R: Read>(
+		&self,
+		reader: R,
+		extraction: &mut Extraction,
+	) -> anyhow::Result<()> {
+		let mut archive = tar::Archive::new(reader);
+
+		for entry in archive.entries()? {
+			self.count_entry(extraction)?;
+
+			let mut entry = entry?;
+			let name = entry.path()?.to_path_buf();
+			let Some(path) = self.target_path(&name)? else {
+				continue;
+			};
+			let path = extraction.destination.join(path);
+			let entry_type = entry.header().entry_type();
+
+			if entry_type.is_dir() {
+				self.prepare(extraction, &name, &path.join("."))?;
+				extraction.extracted += 1;
+				continue;
+			}
+
+			let parent = self.prepare(extraction, &name, &path)?;
+
+			if entry_type.is_symlink() {
+				let target = entry
+					.link_name()?
+					.ok_or_else(|| Self::outside(&name))?
+					.to_path_buf();
+				self.check_symlink(extraction, &name, &parent, &target)?;
+				entry.unpack(&path)?;
+				extraction.symlinks.push(path);
+			} else if entry_type.is_hard_link() {
+				// Hard link targets are archive paths and need the same mapping.
+				let Some(target) = entry
+					.link_name()?
+					.map(|target| self.target_path(&target))
+					.transpose()?
+					.flatten()
+				else {
+					continue;
+				};
+				// And may lead through symlinks extracted earlier.
+				let target = dunce::canonicalize(extraction.destination.join(target))
+					.map_err(|_| Self::outside(&name))?;
+				if !target.starts_with(&extraction.destination) {
+					return Err(Self::outside(&name));
+				}
+				fs::hard_link(target, &path)?;
+			} else if entry_type.is_file() {
+				self.write_file(extraction, &mut entry, &path)?;
+
+				#[cfg(unix)]
+				if let Ok(mode) = entry.header().mode() {
+					use std::os::unix::fs::PermissionsExt;
+					fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777))?;
+				}
+			} else {
+				// Devices, fifos and the like have no business in a dependency.
+				continue;
+			}
+
+			extraction.extracted += 1;
+		}
+
+		Ok(())
+	}
+}
@@ -1,18 +1,101 @@
 use crate::lib::data::environment::Environment;
 use crate::lib::util::hash_string;
-use bzip2::read::{BzDecoder, BzEncoder};
-use bzip2::Compression;
-use std::fs::File;
+use crate::lib::util::size::Size;
+use crate::lib::util::store::{Blob, Store};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::File;
 use std::{fs, io, path::PathBuf, str::FromStr};
 use toml::Table;
 
+/*
+ * What a hashed directory in .cache was created for.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+struct IndexEntry {
+	key: String,
+	last_used: u64,
+}
+
+enum Location {
+	// Name of the directory in the project's .cache.
+	Project(String),
+	Shared(Blob),
+}
+
+/*
+ * Something `numake cache` can show and remove.
+ */
+pub struct CacheEntry {
+	pub key: String,
+	pub size: u64,
+	pub last_used: u64,
+	location: Location,
+}
+
+impl CacheEntry {
+	pub fn is_shared(&self) -> bool {
+		matches!(self.location, Location::Shared(_))
+	}
+}
+
 #[derive(Default)]
 pub struct Cache {
 	user_values: Arc<Mutex<Table>>,
 
 	toml: Arc<Mutex<Table>>,
 	directory: PathBuf,
+
+	// Hashed directory names -> keys they were created for, in index.toml.
+	index: Arc<Mutex<BTreeMap<String, IndexEntry>>>,
+
+	// Where file contents live, shared with other projects.
+	store: Store,
+}
+
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|time| time.as_secs())
+		.unwrap_or(0)
+}
+
+fn directory_size(path: &Path) -> u64 {
+	let Ok(meta) = path.symlink_metadata() else {
+		return 0;
+	};
+	if !meta.is_dir() {
+		return meta.len();
+	}
+
+	fs::read_dir(path)
+		.map(|entries| {
+			entries
+				.flatten()
+				.map(|entry| directory_size(&entry.path()))
+				.sum()
+		})
+		.unwrap_or(0)
+}
+
+/*
+ * Whether a cache key belongs to a URL: archives add their options after
+ * a '#', git checkouts are "git:<url>@<commit>".
+ */
+fn key_matches(
+	key: &str,
+	target: &str,
+) -> bool {
+	["", "git:"].iter().any(|prefix| {
+		let target = prefix.to_string() + target;
+		key == target
+			|| key
+				.strip_prefix(&target)
+				.is_some_and(|rest| rest.starts_with(['#', '@']))
+	})
 }
 
 impl Cache {
@@ -35,60 +118,174 @@ impl Cache {
 				table.get("workspace").unwrap().as_table().unwrap().clone();
 		}
 
+		let index_path = directory.join("index.toml");
+		let index = match index_path.exists() {
+			true => toml::from_str(&fs::read_to_string(&index_path)?)?,
+			false => BTreeMap::new(),
+		};
+
 		Ok(Cache {
 			toml: Arc::new(Mutex::new(table)),
 			user_values: Arc::new(Mutex::new(user_values)),
 			directory,
+			index: Arc::new(Mutex::new(index)),
+			store: Store::new(
+				&environment.cache_directory,
+				environment.config.cache.codec,
+			)?,
 		})
 	}
 
+	fn touch(
+		&self,
+		name: &str,
+	) {
+		(*self.index.lock().unwrap()).insert(
+			hash_string(name),
+			IndexEntry {
+				key: name.to_string(),
+				last_used: now(),
+			},
+		);
+	}
+
+	fn file_key(name: &str) -> String {
+		"file:".to_string() + name
+	}
+
+	/*
+	 * The blob this project last stored under the name, otherwise the
+	 * one any project stored under it.
+	 */
+	pub fn get_file_hash(
+		&mut self,
+		name: &str,
+	) -> Option<String> {
+		self.get_value(&Self::file_key(name))
+			.and_then(|value| value.as_str().map(|str| str.to_string()))
+			.filter(|sha256| self.store.contains(sha256))
+			.or_else(|| self.store.lookup(name))
+	}
+
+	/*
+	 * Stores data every project sees under the same name, for names that
+	 * already say what the contents are (e.g. content hashes). Returns the
+	 * sha256 of the data.
+	 */
 	pub fn write_file<T: AsRef<[u8]>>(
 		&mut self,
-		name: &String,
+		name: &str,
 		data: T,
-	) -> anyhow::Result<PathBuf> {
-		let file_path = self.directory.join(hash_string(name) + ".file");
-		let mut compressor = BzEncoder::new(data.as_ref(), Compression::best());
-		io::copy(&mut compressor, &mut File::create(file_path.clone())?)?;
-		Ok(file_path)
+	) -> anyhow::Result<String> {
+		let sha256 = self.store.put(data.as_ref())?;
+		self.store.link(name, &sha256)?;
+
+		Ok(sha256)
 	}
 
 	pub fn read_file(
 		&mut self,
-		name: &String,
+		name: &str,
 	) -> anyhow::Result<Vec<u8>> {
-		let file_path = self.directory.join(hash_string(name) + ".file");
-		let mut buf = Vec::new();
-		let mut decoder = BzDecoder::new(File::open(file_path)?);
-		io::copy(&mut decoder, &mut buf)?;
-
-		Ok(buf)
+		match self.store.lookup(name) {
+			Some(sha256) => self.store.get(&sha256),
+			None => Err(anyhow::anyhow!("{} isn't cached!", name)),
+		}
 	}
 
-	pub fn check_file_exists(
+	/*
+	 * Moves a file into the cache under the name and returns its sha256.
+	 */
+	pub fn store_file(
 		&mut self,
-		name: &String,
+		name: &str,
+		source: &Path,
+	) -> anyhow::Result<String> {
+		let sha256 = self.store.put_file(source)?;
+		self.store.link(name, &sha256)?;
+		self.set_value(&Self::file_key(name), toml::Value::from(sha256.clone()))?;
+
+		Ok(sha256)
+	}
+
+	/*
+	 * Whether contents with this sha256 were stored under any name.
+	 */
+	pub fn has_blob(
+		&self,
+		sha256: &str,
 	) -> bool {
-		self.directory.join(hash_string(name) + ".file").exists()
+		self.store.contains(sha256)
+	}
+
+	pub fn open_blob(
+		&self,
+		sha256: &str,
+	) -> anyhow::Result<Box<dyn io::Read>> {
+		self.store.open(sha256)
+	}
+
+	/*
+	 * The blob's file if it can be read in place, see Store::raw_path.
+	 */
+	pub fn get_blob_path(
+		&self,
+		sha256: &str,
+	) -> Option<PathBuf> {
+		self.store.raw_path(sha256)
+	}
+
+	/*
+	 * Where an unfinished download is kept so it can be resumed.
+	 */
+	pub fn get_partial_path(
+		&self,
+		name: &str,
+	) -> PathBuf {
+		self.store.partial_path(name)
+	}
+
+	pub fn lock_partial(
+		&self,
+		name: &str,
+		on_wait: impl FnOnce(),
+	) -> anyhow::Result<File> {
+		self.store.lock_partial(name, on_wait)
 	}
 
 	pub fn get_dir(
 		&mut self,
-		name: &String,
+		name: &str,
 	) -> anyhow::Result<PathBuf> {
 		let dir = self.directory.join(hash_string(name));
 		if !dir.exists() {
 			fs::create_dir_all(&dir)?;
 		}
 
+		self.touch(name);
 		Ok(dir)
 	}
 
+	/*
+	 * Where get_dir would put the directory, without creating it.
+	 */
+	pub fn dir_path(
+		&self,
+		name: &str,
+	) -> PathBuf {
+		self.directory.join(hash_string(name))
+	}
+
 	pub fn check_dir_exists(
 		&mut self,
-		name: &String,
+		name: &str,
 	) -> bool {
-		self.directory.join(hash_string(name)).exists()
+		let exists = self.directory.join(hash_string(name)).exists();
+		if exists {
+			self.touch(name);
+		}
+
+		exists
 	}
 
 	pub fn set_value(
@@ -104,29 +301,12 @@ impl Cache {
 		&mut self,
 		key: &str,
 	) -> Option<toml::Value> {
-		match (*self.toml.lock().unwrap()).get(&hash_string(key)) {
-			Some(v) => Some(v.clone()),
-			None => None,
-		}
-	}
-
-	pub fn pop_value(
-		&mut self,
-		key: &String,
-	) {
-		(*self.toml.lock().unwrap()).remove(&hash_string(key));
-	}
-
-	pub fn check_key_exists(
-		&mut self,
-		key: &String,
-	) -> bool {
-		(*self.toml.lock().unwrap()).contains_key(&hash_string(key))
+		(*self.toml.lock().unwrap()).get(&hash_string(key)).cloned()
 	}
 
 	pub fn set_user_value(
 		&mut self,
-		key: &String,
+		key: &str,
 		val: toml::Value,
 	) -> anyhow::Result<()> {
 		(*self.user_values.lock().unwrap()).insert(hash_string(key), val);
@@ -135,19 +315,9 @@ impl Cache {
 
 	pub fn get_user_value(
 		&mut self,
-		key: &String,
+		key: &str,
 	) -> Option<toml::Value> {
-		match (*self.user_values.lock().unwrap()).get(&hash_string(key)) {
-			Some(v) => Some(v.clone()),
-			None => None,
-		}
-	}
-
-	pub fn pop_user_value(
-		&mut self,
-		key: &String,
-	) {
-		(*self.user_values.lock().unwrap()).remove(&hash_string(key));
+		(*self.user_values.lock().unwrap()).get(&hash_string(key)).cloned()
 	}
 
 	pub fn flush(&mut self) -> io::Result<()> {
@@ -155,7 +325,147 @@ impl Cache {
 			"workspace".to_string(),
 			toml::Value::Table((*self.user_values.lock().unwrap()).clone()),
 		);
-		fs::write(self.directory.join("cache.toml"), (*self.toml.lock().unwrap()).to_string())
+		fs::write(self.directory.join("cache.toml"), (*self.toml.lock().unwrap()).to_string())?;
+
+		let index = toml::to_string(&*self.index.lock().unwrap()).map_err(io::Error::other)?;
+		fs::write(self.directory.join("index.toml"), index)
+	}
+
+	pub fn get_store_directory(&self) -> &Path {
+		self.store.directory()
+	}
+
+	/*
+	 * The project's extracted directories and every shared download, least
+	 * recently used first. Directories from before the index existed are
+	 * listed by their hash.
+	 */
+	pub fn entries(&mut self) -> anyhow::Result<Vec<CacheEntry>> {
+		let index = (*self.index.lock().unwrap()).clone();
+		let mut entries = Vec::new();
+
+		for entry in fs::read_dir(&self.directory)? {
+			let entry = entry?;
+			let name = entry.file_name().to_string_lossy().to_string();
+			if !entry.file_type()?.is_dir() {
+				continue;
+			}
+
+			let (key, last_used) = match index.get(&name) {
+				Some(indexed) => (indexed.key.clone(), indexed.last_used),
+				None => (
+					format!("<unknown {}>", name),
+					entry
+						.metadata()?
+						.modified()?
+						.duration_since(UNIX_EPOCH)
+						.map(|time| time.as_secs())
+						.unwrap_or(0),
+				),
+			};
+
+			entries.push(CacheEntry {
+				key,
+				size: directory_size(&entry.path()),
+				last_used,
+				location: Location::Project(name),
+			});
+		}
+
+		for blob in self.store.blobs()? {
+			entries.push(CacheEntry {
+				key: match blob.names.is_empty() {
+					true => format!("<unnamed {}>", blob.sha256),
+					false => blob.names.join(", "),
+				},
+				size: blob.size,
+				last_used: blob.last_used,
+				location: Location::Shared(blob),
+			});
+		}
+
+		entries.sort_by_key(|entry| entry.last_used);
+		Ok(entries)
+	}
+
+	fn remove_entry(
+		&mut self,
+		entry: &CacheEntry,
+	) -> anyhow::Result<()> {
+		match &entry.location {
+			Location::Project(name) => {
+				fs::remove_dir_all(self.directory.join(name))?;
+				(*self.index.lock().unwrap()).remove(name);
+			}
+			Location::Shared(blob) => self.store.remove(blob)?,
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * Removes whatever nothing reads anymore (compressed files from before
+	 * the shared cache), then the least recently used entries until the
+	 * rest fits in max_size. Returns what was removed.
+	 */
+	pub fn prune(
+		&mut self,
+		max_size: Option<Size>,
+	) -> anyhow::Result<Vec<CacheEntry>> {
+		for entry in fs::read_dir(&self.directory)? {
+			let path = entry?.path();
+			let legacy = path
+				.extension()
+				.is_some_and(|extension| extension == "file" || extension == "part");
+			if legacy && path.is_file() {
+				fs::remove_file(path)?;
+			}
+		}
+
+		let entries = self.entries()?;
+		let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+		let mut removed = Vec::new();
+
+		if let Some(max_size) = max_size {
+			for entry in entries {
+				if total <= max_size.0 {
+					break;
+				}
+
+				self.remove_entry(&entry)?;
+				total -= entry.size;
+				removed.push(entry);
+			}
+		}
+
+		self.flush()?;
+		Ok(removed)
+	}
+
+	/*
+	 * Removes everything cached for a URL or cache key.
+	 */
+	pub fn remove(
+		&mut self,
+		target: &str,
+	) -> anyhow::Result<Vec<CacheEntry>> {
+		let mut removed = Vec::new();
+		for entry in self.entries()? {
+			let matches = match &entry.location {
+				Location::Project(_) => key_matches(&entry.key, target),
+				Location::Shared(blob) => {
+					blob.names.iter().any(|name| key_matches(name, target))
+				}
+			};
+
+			if matches {
+				self.remove_entry(&entry)?;
+				removed.push(entry);
+			}
+		}
+
+		self.flush()?;
+		Ok(removed)
 	}
 }
 
@@ -165,6 +475,8 @@ impl Clone for Cache {
 			user_values: Arc::clone(&self.user_values),
 			toml: Arc::clone(&self.toml),
 			directory: self.directory.clone(),
+			index: Arc::clone(&self.index),
+			store: self.store.clone(),
 		}
 	}
 }
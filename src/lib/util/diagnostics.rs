@@ -0,0 +1,236 @@
+use crate::lib::ui::format::{error, info::Info};
+use crate::lib::ui::UI;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiagnosticFormat {
+	/// Only print the per-file summary.
+	#[default]
+	Human,
+	/// Print every diagnostic as a JSON array on stdout.
+	Json,
+	/// Print every diagnostic as a GitHub Actions annotation on stdout.
+	Github,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+	Warning,
+	Error,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+	pub file: String,
+	pub line: u32,
+	pub column: Option<u32>,
+	pub level: DiagnosticLevel,
+	pub code: Option<String>,
+	pub message: String,
+}
+
+impl Diagnostic {
+	/*
+	 * GCC & Clang:
+	 * file:line:col: warning: message [-Wflag]
+	 * file:line: error: message
+	 */
+	pub fn parse_gcc(line: &str) -> Option<Diagnostic> {
+		let (level, marker) = [
+			(DiagnosticLevel::Warning, ": warning: "),
+			(DiagnosticLevel::Error, ": fatal error: "),
+			(DiagnosticLevel::Error, ": error: "),
+		]
+		.into_iter()
+		.find(|(_, marker)| line.contains(marker))?;
+
+		let (location, message) = line.split_once(marker)?;
+
+		// Split from the right, file names may contain colons (C:\...).
+		let mut parts = location.rsplitn(3, ':');
+		let last: u32 = parts.next()?.trim().parse().ok()?;
+		let (file, line, column) = match parts.next()?.trim().parse::<u32>() {
+			Ok(line) => (parts.next()?, line, Some(last)),
+			Err(_) => (location.rsplit_once(':')?.0, last, None),
+		};
+
+		let (message, code) = match message.rsplit_once(" [") {
+			Some((message, flag)) if flag.ends_with(']') => (
+				message,
+				Some(flag.trim_end_matches(']').to_string()),
+			),
+			_ => (message, None),
+		};
+
+		Some(Diagnostic {
+			file: file.trim().to_string(),
+			line,
+			column,
+			level,
+			code,
+			message: message.trim().to_string(),
+		})
+	}
+
+	/*
+	 * MSVC:
+	 * file(line): warning C4996: message
+	 * file(line,col): error C2065: message
+	 */
+	pub fn parse_msvc(line: &str) -> Option<Diagnostic> {
+		let (location, rest) = line.split_once("): ")?;
+		let (file, position) = location.rsplit_once('(')?;
+
+		let (line_str, column) = match position.split_once(',') {
+			Some((line, column)) => (line, column.trim().parse().ok()),
+			None => (position, None),
+		};
+
+		let (level, rest) = if let Some(rest) = rest.strip_prefix("warning ") {
+			(DiagnosticLevel::Warning, rest)
+		} else if let Some(rest) = rest.strip_prefix("fatal error ") {
+			(DiagnosticLevel::Error, rest)
+		} else if let Some(rest) = rest.strip_prefix("error ") {
+			(DiagnosticLevel::Error, rest)
+		} else {
+			return None;
+		};
+
+		let (code, message) = rest.split_once(": ")?;
+
+		Some(Diagnostic {
+			file: file.trim().to_string(),
+			line: line_str.trim().parse().ok()?,
+			column,
+			level,
+			code: Some(code.trim().to_string()),
+			message: message.trim().to_string(),
+		})
+	}
+
+	fn github_annotation(&self) -> String {
+		let escape_property = |str: &str| {
+			Self::escape_github(str).replace(':', "%3A").replace(',', "%2C")
+		};
+
+		let mut properties =
+			vec![format!("file={}", escape_property(&self.file))];
+		properties.push(format!("line={}", self.line));
+		if let Some(column) = self.column {
+			properties.push(format!("col={column}"));
+		}
+		if let Some(code) = &self.code {
+			properties.push(format!("title={}", escape_property(code)));
+		}
+
+		format!(
+			"::{} {}::{}",
+			match self.level {
+				DiagnosticLevel::Warning => "warning",
+				DiagnosticLevel::Error => "error",
+			},
+			properties.join(","),
+			Self::escape_github(&self.message)
+		)
+	}
+
+	fn escape_github(str: &str) -> String {
+		str.replace('%', "%25")
+			.replace('\r', "%0D")
+			.replace('\n', "%0A")
+	}
+}
+
+/*
+ * Collects the diagnostics of every command run during a build. Headers
+ * included by many translation units produce the same warning over and
+ * over again, so duplicates are dropped on insertion.
+ */
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+	seen: Arc<Mutex<HashSet<Diagnostic>>>,
+	diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+}
+
+impl Diagnostics {
+	pub fn new() -> Self {
+		Diagnostics::default()
+	}
+
+	/*
+	 * Returns the diagnostics that haven't been seen before.
+	 */
+	pub fn collect(
+		&self,
+		output: &str,
+		parser: fn(&str) -> Option<Diagnostic>,
+	) -> Vec<Diagnostic> {
+		let mut new_diagnostics = Vec::new();
+		for diagnostic in output.lines().filter_map(parser) {
+			if (*self.seen.lock().unwrap()).insert(diagnostic.clone()) {
+				(*self.diagnostics.lock().unwrap()).push(diagnostic.clone());
+				new_diagnostics.push(diagnostic);
+			}
+		}
+
+		new_diagnostics
+	}
+
+	pub fn report(
+		&self,
+		ui: &UI,
+		format: DiagnosticFormat,
+	) -> anyhow::Result<()> {
+		let diagnostics = (*self.diagnostics.lock().unwrap()).clone();
+		if diagnostics.is_empty() {
+			return Ok(());
+		}
+
+		let mut per_file: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+		for diagnostic in &diagnostics {
+			let counts = per_file.entry(&diagnostic.file).or_default();
+			match diagnostic.level {
+				DiagnosticLevel::Warning => counts.0 += 1,
+				DiagnosticLevel::Error => counts.1 += 1,
+			}
+		}
+
+		for (file, (warnings, errors)) in &per_file {
+			let summary =
+				format!("{file}: {warnings} warning(s), {errors} error(s)");
+			if *errors > 0 {
+				ui.println(summary, error::Error::default());
+			} else {
+				ui.println(summary, Info::default());
+			}
+		}
+
+		// With --message-format=json stdout is the event stream, which has
+		// the diagnostics already.
+		let mut out: Box<dyn Write> = match ui.is_json() {
+			true => Box::new(io::stderr()),
+			false => Box::new(io::stdout()),
+		};
+
+		match format {
+			DiagnosticFormat::Human => {}
+
+			DiagnosticFormat::Json => {
+				writeln!(out, "{}", serde_json::to_string(&diagnostics)?)?
+			}
+
+			DiagnosticFormat::Github => {
+				for diagnostic in &diagnostics {
+					writeln!(out, "{}", diagnostic.github_annotation())?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
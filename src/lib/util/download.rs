@@ -0,0 +1,258 @@
+use crate::lib::data::config::DownloadConfig;
+use crate::lib::runtime::system::System;
+use crate::lib::ui::format::{info::Info, ok, warning};
+use crate::lib::ui::UI;
+use crate::lib::util::error::NuMakeError::DownloadFailed;
+use anyhow::anyhow;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+enum Failure {
+	// Worth another attempt: network errors, timeouts, 5xx, 429.
+	Transient(anyhow::Error),
+	Permanent(anyhow::Error),
+}
+
+impl From<reqwest::Error> for Failure {
+	fn from(err: reqwest::Error) -> Self {
+		Failure::Transient(err.into())
+	}
+}
+
+// Writing the .part file, another attempt won't make room on the disk.
+impl From<std::io::Error> for Failure {
+	fn from(err: std::io::Error) -> Self {
+		Failure::Permanent(err.into())
+	}
+}
+
+/*
+ * Streams a URL into a file. Whatever is already in the file is kept and
+ * only the rest is requested, so an interrupted or failed download picks
+ * up where it stopped.
+ */
+pub struct Downloader<'a> {
+	config: &'a DownloadConfig,
+	ui: &'a UI,
+}
+
+impl<'a> Downloader<'a> {
+	pub fn new(
+		config: &'a DownloadConfig,
+		ui: &'a UI,
+	) -> Self {
+		Downloader { config, ui }
+	}
+
+	pub fn fetch(
+		&self,
+		url: &str,
+		partial: &Path,
+	) -> anyhow::Result<()> {
+		let client = Client::builder()
+			.timeout(Duration::from_secs(self.config.timeout))
+			.connect_timeout(Duration::from_secs(self.config.connect_timeout))
+			.build()?;
+
+		let mut attempt = 0;
+		loop {
+			System::check_interrupted()?;
+
+			let err = match self.attempt(&client, url, partial) {
+				Ok(()) => return Ok(()),
+				Err(Failure::Permanent(err)) => return Err(err),
+				Err(Failure::Transient(err)) => err,
+			};
+
+			if attempt >= self.config.retries || System::interrupted() {
+				return Err(err.context(DownloadFailed {
+					url: url.to_string(),
+					attempts: attempt + 1,
+				}));
+			}
+
+			let delay = self.config.backoff * 2f64.powi(attempt as i32);
+			attempt += 1;
+			self.ui.println(
+				format!(
+					"{} Retrying in {:.1}s ({}/{}) [{}]",
+					err, delay, attempt, self.config.retries, url
+				),
+				warning::Warning::default(),
+			);
+			thread::sleep(Duration::from_secs_f64(delay.max(0.0)));
+		}
+	}
+
+	fn attempt(
+		&self,
+		client: &Client,
+		url: &str,
+		partial: &Path,
+	) -> Result<(), Failure> {
+		let offset = partial.metadata().map(|meta| meta.len()).unwrap_or(0);
+
+		let mut request = self.authorize(client.get(url), url)?;
+		if offset > 0 {
+			request = request.header(RANGE, format!("bytes={offset}-"));
+		}
+
+		let response = request.send()?;
+		let status = response.status();
+
+		// The server has nothing past our offset, the file is probably stale.
+		if status == StatusCode::RANGE_NOT_SATISFIABLE {
+			File::create(partial)?;
+			return Err(Failure::Transient(anyhow!(
+				"Server rejected resuming the download at {} bytes!",
+				offset
+			)));
+		}
+
+		if !status.is_success() {
+			let err = anyhow!("Server responded with {}! [{}]", status, url);
+			return Err(
+				if status.is_server_error()
+					|| status == StatusCode::TOO_MANY_REQUESTS
+				{
+					Failure::Transient(err)
+				} else {
+					Failure::Permanent(err)
+				},
+			);
+		}
+
+		let resumed = status == StatusCode::PARTIAL_CONTENT
+			&& Self::range_start(&response) == Some(offset);
+		let offset = if resumed { offset } else { 0 };
+
+		if resumed {
+			self.ui.println(
+				format!("Resuming download at {} bytes. [{}]", offset, url),
+				Info::default(),
+			);
+		} else {
+			self.ui.println(
+				format!("Server responded with {}! [{}]", status, url),
+				ok::Ok::default(),
+			);
+		}
+
+		let mut file = if resumed {
+			OpenOptions::new().append(true).open(partial)?
+		} else {
+			File::create(partial)?
+		};
+
+		self.stream(response, &mut file, offset, url)?;
+		Ok(())
+	}
+
+	fn stream(
+		&self,
+		mut response: Response,
+		file: &mut File,
+		offset: u64,
+		url: &str,
+	) -> Result<(), Failure> {
+		// Read before consuming the body, it shrinks as the body is read.
+		let total = response.content_length().map(|length| length + offset);
+
+		let bar = self.ui.create_download_bar(
+			total,
+			format!("Downloading... [{}]", url),
+		);
+		bar.set_position(offset);
+
+		let mut buf = vec![0u8; 64 * 1024];
+		let result = loop {
+			if System::interrupted() {
+				break System::check_interrupted().map_err(Failure::Permanent);
+			}
+
+			match response.read(&mut buf) {
+				Ok(0) => break Ok(()),
+				Ok(read) => {
+					if let Err(err) = file.write_all(&buf[..read]) {
+						break Err(err.into());
+					}
+					bar.inc(read as u64);
+				}
+				// The connection, not the disk.
+				Err(err) => break Err(Failure::Transient(err.into())),
+			}
+		};
+
+		bar.finish_and_clear();
+		result?;
+
+		if let Some(total) = total {
+			if file.metadata()?.len() != total {
+				return Err(Failure::Transient(anyhow!(
+					"Connection closed before the download finished!"
+				)));
+			}
+		}
+
+		Ok(())
+	}
+
+	/*
+	 * "Content-Range: bytes 100-999/1000" -> 100
+	 */
+	fn range_start(response: &Response) -> Option<u64> {
+		let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+		range
+			.strip_prefix("bytes ")?
+			.split_once('-')?
+			.0
+			.trim()
+			.parse()
+			.ok()
+	}
+
+	fn authorize(
+		&self,
+		mut request: reqwest::blocking::RequestBuilder,
+		url: &str,
+	) -> Result<reqwest::blocking::RequestBuilder, Failure> {
+		let parsed = reqwest::Url::parse(url).map_err(|err| Failure::Permanent(err.into()))?;
+		let Some(host) = parsed.host_str().and_then(|host| self.config.hosts.get(host))
+		else {
+			return Ok(request);
+		};
+
+		let read_env = |name: &str| match env::var(name) {
+			Ok(value) => Some(value),
+			Err(_) => {
+				self.ui.println(
+					format!(
+						"Environment variable {} isn't set, sending the request without it. [{}]",
+						name, url
+					),
+					warning::Warning::default(),
+				);
+				None
+			}
+		};
+
+		if let Some(token) = host.bearer_env.as_deref().and_then(read_env) {
+			request = request.bearer_auth(token);
+		}
+
+		for (header, variable) in &host.headers {
+			if let Some(value) = read_env(variable) {
+				request = request.header(header, value);
+			}
+		}
+
+		Ok(request)
+	}
+}
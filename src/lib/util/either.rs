@@ -1,5 +1,4 @@
-use std::error::Error;
-use mlua::{FromLua, FromLuaMulti, IntoLuaMulti, Lua, Value};
+use mlua::{FromLua, Lua, Value};
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum Either<A, B> {
@@ -11,9 +10,9 @@ impl<A: FromLua, B: FromLua> FromLua for Either<A, B> {
     fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
         match A::from_lua(value.clone(), lua) {
             Ok(a) => Ok(Either::First(a)),
-            Err(e) => match B::from_lua(value, lua) {
+            Err(_) => match B::from_lua(value, lua) {
                 Ok(b) => Ok(Either::Second(b)),
-                Err(e2) => Err(mlua::Error::runtime("Could not parse either value!"))
+                Err(_) => Err(mlua::Error::runtime("Could not parse either value!"))
             }
         }
     }
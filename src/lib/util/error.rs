@@ -1,23 +1,18 @@
 // SHUT THE FUCK UP THIS IS SNAKE CASE
 
+use crate::lib::util::size::Size;
 use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum NuMakeError {
-	#[error("Path exits working directory!")]
-	PathOutsideWorkingDirectory,
+	#[error("{0} is outside the project and numake_build! Other directories can be made readable under [paths] in numake.toml.")]
+	PathOutsideWorkingDirectory(String),
+
+	#[error("{0} is outside numake_build! Scripts can only write inside numake_build.")]
+	PathNotWritable(String),
 
 	#[error("Tried to copy asset to path outside output directory!")]
 	AssetCopyPathOutsideWorkingDirectory,
 
-	#[error("No compiler specified/found!")]
-	ToolsetCompilerNull,
-
-	#[error("No linker specified/found!")]
-	ToolsetLinkerNull,
-
-	#[error("Attempted to add_file with a directory path! Use add_dir instead!")]
-	AddFileIsDirectory,
-
 	#[error("Task not found!")]
 	TaskNotFound,
 
@@ -25,5 +20,130 @@ pub enum NuMakeError {
 	MsvcWindowsOnly,
 
 	#[error("Visual C/C++ installation not found! Make sure you have Visual Studio/Build Tools installed!")]
-	VcNotFound 
-}
\ No newline at end of file
+	VcNotFound,
+
+	#[error("Build interrupted!")]
+	Interrupted,
+
+	#[error("{program} exited with {status}!")]
+	CommandFailed {
+		program: String,
+		status: String,
+	},
+
+	#[error("{0} files failed to compile!")]
+	CompilationFailed(usize),
+
+	#[error("Checksum mismatch for {url}!\n  expected: {expected}\n  actual:   {actual}")]
+	ChecksumMismatch {
+		url: String,
+		expected: String,
+		actual: String,
+	},
+
+	#[error("Couldn't detect the archive format of {0}!")]
+	UnknownArchiveFormat(String),
+
+	#[error("Directory {subdir} not found in archive {url}!")]
+	ArchiveSubdirNotFound {
+		url: String,
+		subdir: String,
+	},
+
+	#[error("Archive entry {entry} escapes the extraction directory!")]
+	ArchiveEntryOutsideDestination {
+		entry: String,
+	},
+
+	#[error("Archive {url} has more than {limit} entries!")]
+	ArchiveTooManyEntries {
+		url: String,
+		limit: usize,
+	},
+
+	#[error("Archive {url} extracts to more than {limit}!")]
+	ArchiveTooLarge {
+		url: String,
+		limit: Size,
+	},
+
+	#[error("{0} isn't cached and numake is running with --offline!")]
+	OfflineNotCached(String),
+
+	#[error("{0} isn't cached and numake is running with --deny-network!")]
+	NetworkDenied(String),
+
+	#[error("Downloading {url} from {host} isn't allowed! Add the host to allow in the [network] section of numake.toml.")]
+	NetworkHostNotAllowed {
+		host: String,
+		url: String,
+	},
+
+	#[error("Download of {url} failed after {attempts} attempts!")]
+	DownloadFailed {
+		url: String,
+		attempts: u32,
+	},
+
+	#[error("git {args} failed!\n{stderr}")]
+	GitFailed {
+		args: String,
+		stderr: String,
+	},
+
+	#[error("Invalid git repository or revision {0}! Neither can start with '-'.")]
+	InvalidGitArgument(String),
+
+	#[error("Revision {rev} not found in {url}!")]
+	GitRevisionNotFound {
+		url: String,
+		rev: String,
+	},
+
+	#[error("No dependency named {0} was fetched! Pass --task if it's downloaded inside a task.")]
+	DependencyNotFound(String),
+
+	#[error("Patch {patch} failed to apply to {file}: {reason}")]
+	PatchFailed {
+		patch: String,
+		file: String,
+		reason: String,
+	},
+
+	#[error("Module {name} not found! Searched in {searched}.")]
+	ModuleNotFound {
+		name: String,
+		searched: String,
+	},
+
+	#[error("Module {0} must be a relative path inside the project or a module path!")]
+	InvalidModulePath(String),
+
+	#[error("Modules require each other: {0}")]
+	RequireCycle(String),
+
+	#[error("Subproject {0} not found!")]
+	SubprojectNotFound(String),
+
+	#[error("Subprojects {first} and {second} would both be named {name}!")]
+	SubprojectNameTaken {
+		name: String,
+		first: String,
+		second: String,
+	},
+
+	#[error("Subprojects include each other: {0}")]
+	SubprojectCycle(String),
+
+	#[error("The script ran out of memory! Raise memory_limit in the [script] section of numake.toml if it really needs more.")]
+	ScriptMemoryLimit,
+
+	#[error("The script ran for more than {0} seconds! Raise time_limit in the [script] section of numake.toml if it really needs longer.")]
+	ScriptTimeLimit(u64),
+
+	#[error("{0} is provided by numake and can't be replaced!")]
+	ReadOnlyGlobal(String),
+
+	#[error("No project.lua, project.luau or numake.lua found in {0} or any directory above it!")]
+	ProjectScriptNotFound(String),
+}
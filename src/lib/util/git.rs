@@ -0,0 +1,128 @@
+use crate::lib::util::error::NuMakeError::GitFailed;
+use anyhow::anyhow;
+use std::path::Path;
+use std::process::Command;
+
+/*
+ * Thin wrapper around the git executable, so clones use the user's
+ * credential helpers, SSH keys and proxy settings like any other git
+ * command would.
+ */
+pub struct Git;
+
+impl Git {
+	pub fn run(
+		dir: &Path,
+		args: &[&str],
+	) -> anyhow::Result<String> {
+		let output = Command::new("git")
+			.args(args)
+			.current_dir(dir)
+			// A credential prompt would hang behind the progress bars.
+			.env("GIT_TERMINAL_PROMPT", "0")
+			.output()
+			.map_err(|err| anyhow!("Couldn't run git, is it installed? {}", err))?;
+
+		if !output.status.success() {
+			return Err(anyhow!(GitFailed {
+				args: args.join(" "),
+				stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+			}));
+		}
+
+		Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+	}
+
+	/*
+	 * Full hash of the commit a revision points to, None if the
+	 * repository doesn't have it.
+	 */
+	pub fn resolve(
+		repo: &Path,
+		rev: &str,
+	) -> Option<String> {
+		Self::run(
+			repo,
+			&["rev-parse", "--verify", "--quiet", &format!("{rev}^{{commit}}")],
+		)
+		.ok()
+		.filter(|commit| !commit.is_empty())
+	}
+
+	/*
+	 * Fetches every branch and tag into a bare repository, branches end up
+	 * under refs/remotes/origin like in a regular clone.
+	 */
+	pub fn fetch(
+		repo: &Path,
+		url: &str,
+	) -> anyhow::Result<()> {
+		if !repo.join("HEAD").exists() {
+			Self::run(repo, &["init", "--bare", "--quiet"])?;
+		}
+
+		Self::run(
+			repo,
+			&[
+				"fetch",
+				"--quiet",
+				"--force",
+				"--prune",
+				"--tags",
+				"--",
+				url,
+				"+refs/heads/*:refs/remotes/origin/*",
+				"+HEAD:refs/remotes/origin/HEAD",
+			],
+		)?;
+
+		Ok(())
+	}
+
+	/*
+	 * Commits that aren't on any branch or tag can still be fetched
+	 * directly from most servers. They get a ref of their own so they
+	 * survive garbage collection.
+	 */
+	pub fn fetch_commit(
+		repo: &Path,
+		url: &str,
+		commit: &str,
+	) -> Option<String> {
+		let refspec = format!("+{commit}:refs/numake/{commit}");
+		Self::run(repo, &["fetch", "--quiet", "--", url, &refspec]).ok()?;
+		Self::resolve(repo, commit)
+	}
+
+	/*
+	 * Name, path and URL of every submodule in .gitmodules, the URLs as
+	 * they're written there.
+	 */
+	pub fn submodules(dir: &Path) -> anyhow::Result<Vec<(String, String, String)>> {
+		let gitmodules = |args: &[&str]| {
+			Self::run(dir, &[&["config", "--file", ".gitmodules"], args].concat())
+		};
+
+		// Exits with an error when nothing matches.
+		let paths = gitmodules(&["--null", "--get-regexp", r"^submodule\..*\.path$"])
+			.unwrap_or_default();
+
+		let mut submodules = Vec::new();
+		for entry in paths.split('\0').filter(|entry| !entry.is_empty()) {
+			let Some((key, path)) = entry.split_once('\n') else {
+				continue;
+			};
+			let Some(name) = key
+				.strip_prefix("submodule.")
+				.and_then(|key| key.strip_suffix(".path"))
+			else {
+				continue;
+			};
+
+			let url = gitmodules(&["--get", &format!("submodule.{name}.url")])?;
+			submodules.push((name.to_string(), path.to_string(), url));
+		}
+
+		Ok(submodules)
+	}
+}
@@ -0,0 +1,234 @@
+use crate::lib::data::pin::Pin;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// An entry's URL and, for git sources, the requested rev.
+type LockKey = (String, Option<String>);
+
+const HEADER: &str =
+	"# Generated by numake, refresh entries with `numake update [name]`.\n\n";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+	pub name: String,
+	pub url: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rev: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sha256: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub commit: Option<String>,
+}
+
+impl LockEntry {
+	pub fn pin(&self) -> Option<Pin> {
+		match (&self.sha256, &self.commit) {
+			(Some(hash), _) => Some(Pin::Sha256(hash.clone())),
+			(None, Some(commit)) => Some(Pin::Commit(commit.clone())),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockToml {
+	#[serde(default, rename = "source")]
+	sources: Vec<LockEntry>,
+}
+
+/*
+ * numake.lock, next to the project script. Records what every download
+ * and git dependency resolved to, later runs are verified against it.
+ * Entries are keyed by URL, plus the requested rev for git sources.
+ */
+#[derive(Clone)]
+pub struct Lockfile {
+	path: PathBuf,
+	entries: Arc<Mutex<Vec<LockEntry>>>,
+	seen: Arc<Mutex<HashSet<LockKey>>>,
+
+	// `numake update` targets, empty means everything.
+	refresh: Option<Vec<String>>,
+}
+
+impl Lockfile {
+	pub fn new(
+		project_file: &Path,
+		refresh: Option<Vec<String>>,
+	) -> anyhow::Result<Self> {
+		let path = project_file
+			.parent()
+			.unwrap_or(Path::new("."))
+			.join("numake.lock");
+
+		let toml: LockToml = if path.exists() {
+			toml::from_str(&fs::read_to_string(&path)?).map_err(|err| {
+				anyhow::anyhow!("Invalid lockfile {}!\n{}", path.display(), err)
+			})?
+		} else {
+			LockToml::default()
+		};
+
+		Ok(Lockfile {
+			path,
+			entries: Arc::new(Mutex::new(toml.sources)),
+			seen: Arc::new(Mutex::new(HashSet::new())),
+			refresh,
+		})
+	}
+
+	/*
+	 * The name an entry is refreshed by, the last URL segment without
+	 * archive extensions, e.g. SDL2-2.30.2 for .../SDL2-2.30.2.tar.gz.
+	 */
+	pub fn default_name(url: &str) -> String {
+		let path = url.split(['?', '#']).next().unwrap_or_default();
+		let mut name = path
+			.trim_end_matches(['/', '\\'])
+			.rsplit(['/', '\\', ':'])
+			.next()
+			.unwrap_or(path)
+			.to_string();
+
+		for extension in
+			[".git", ".zip", ".tgz", ".txz", ".tbz2", ".tzst", ".gz", ".xz", ".bz2", ".zst", ".tar"]
+		{
+			if let Some(stripped) = name.strip_suffix(extension) {
+				name = stripped.to_string();
+			}
+		}
+
+		name
+	}
+
+	pub fn should_refresh(
+		&self,
+		name: &str,
+		url: &str,
+	) -> bool {
+		match &self.refresh {
+			Some(names) => {
+				names.is_empty()
+					|| names.iter().any(|target| target == name || target == url)
+			}
+			None => false,
+		}
+	}
+
+	/*
+	 * The locked entry, unless it's being refreshed.
+	 */
+	pub fn get(
+		&self,
+		name: &str,
+		url: &str,
+		rev: Option<&str>,
+	) -> Option<LockEntry> {
+		if self.should_refresh(name, url) {
+			return None;
+		}
+
+		(*self.entries.lock().unwrap())
+			.iter()
+			.find(|entry| entry.url == url && entry.rev.as_deref() == rev)
+			.cloned()
+	}
+
+	/*
+	 * Records what a source resolved to and writes the file if anything
+	 * changed. Returns the previous pin when it was replaced.
+	 */
+	pub fn record(
+		&self,
+		name: &str,
+		url: &str,
+		rev: Option<&str>,
+		pin: &Pin,
+	) -> anyhow::Result<Option<Pin>> {
+		(*self.seen.lock().unwrap()).insert((url.to_string(), rev.map(String::from)));
+
+		let entry = LockEntry {
+			name: name.to_string(),
+			url: url.to_string(),
+			rev: rev.map(String::from),
+			sha256: match pin {
+				Pin::Sha256(hash) => Some(hash.clone()),
+				Pin::Commit(_) => None,
+			},
+			commit: match pin {
+				Pin::Commit(commit) => Some(commit.clone()),
+				Pin::Sha256(_) => None,
+			},
+		};
+
+		let previous = {
+			let mut entries = self.entries.lock().unwrap();
+			let existing = entries
+				.iter_mut()
+				.find(|existing| existing.url == entry.url && existing.rev == entry.rev);
+
+			match existing {
+				Some(existing) if *existing == entry => return Ok(None),
+				Some(existing) => {
+					let previous = existing.pin();
+					*existing = entry;
+					previous
+				}
+				None => {
+					entries.push(entry);
+					None
+				}
+			}
+		};
+
+		self.write()?;
+		Ok(previous)
+	}
+
+	/*
+	 * Whether a `numake update` target matched any source of this run.
+	 */
+	pub fn was_seen(
+		&self,
+		target: &str,
+	) -> bool {
+		let seen = self.seen.lock().unwrap();
+		(*self.entries.lock().unwrap()).iter().any(|entry| {
+			(entry.name == target || entry.url == target)
+				&& seen.contains(&(entry.url.clone(), entry.rev.clone()))
+		})
+	}
+
+	/*
+	 * After refreshing everything, entries no script call asked for
+	 * anymore are dropped.
+	 */
+	pub fn prune_unseen(&self) -> anyhow::Result<()> {
+		let seen = (*self.seen.lock().unwrap()).clone();
+		let removed = {
+			let mut entries = self.entries.lock().unwrap();
+			let before = entries.len();
+			entries.retain(|entry| seen.contains(&(entry.url.clone(), entry.rev.clone())));
+			before != entries.len()
+		};
+
+		if removed {
+			self.write()?;
+		}
+		Ok(())
+	}
+
+	fn write(&self) -> anyhow::Result<()> {
+		let mut sources = (*self.entries.lock().unwrap()).clone();
+		sources.sort_by(|a, b| (&a.url, &a.rev).cmp(&(&b.url, &b.rev)));
+
+		fs::write(
+			&self.path,
+			HEADER.to_string() + &toml::to_string(&LockToml { sources })?,
+		)?;
+		Ok(())
+	}
+}
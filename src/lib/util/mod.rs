@@ -1,17 +1,25 @@
-use std::{collections::HashMap, fs, path::Path, process::Command};
+use std::{env, fs, path::Path};
 
 use anyhow::anyhow;
-use mlua::{Integer, IntoLua, Lua, Value};
-
-use crate::lib::data::flag_type::FlagType;
-use crate::lib::util::either::Either;
-use crate::lib::util::either::Either::{First, Second};
+use mlua::{Integer, IntoLua, Lua};
 use sha256::digest;
 
+pub mod archive;
 pub mod build_cache;
 pub mod cache;
+pub mod diagnostics;
+pub mod download;
 pub mod either;
 pub mod error;
+pub mod git;
+pub mod lockfile;
+pub mod patch;
+pub mod path_policy;
+pub mod script_cache;
+pub mod script_error;
+pub mod size;
+pub mod store;
+pub mod timings;
 
 pub fn hash_string(val: &str) -> String {
 	let mut result = digest(val);
@@ -35,7 +43,7 @@ pub fn into_lua_value(
 			.iter()
 			.map(|val| into_lua_value(lua, val).unwrap_or(mlua::Nil))
 			.collect::<Vec<mlua::Value>>()
-			.into_lua(&lua)?;
+			.into_lua(lua)?;
 	} else if origin.is_table() {
 		dest = mlua::Value::Table(into_lua_table(
 			lua,
@@ -95,14 +103,34 @@ pub fn into_toml_table(origin: &mlua::Table) -> mlua::Result<toml::Table> {
 	Ok(dest)
 }
 
-pub fn to_lua_result<T>(val: anyhow::Result<T>) -> mlua::Result<T> {
-	if val.is_err() {
-		Err(mlua::Error::external(val.err().unwrap()))?
-	} else {
-		Ok(val.ok().unwrap())
+/*
+ * Lua errors raised inside nested scripts are passed on as they are,
+ * anything else is reported with its context.
+ */
+pub fn into_lua_error(err: anyhow::Error) -> mlua::Error {
+	match err.downcast::<mlua::Error>() {
+		Ok(err) => err,
+		Err(err) => mlua::Error::RuntimeError(format!("{:#}", err)),
 	}
 }
 
+/*
+ * Runs `f` with `directory` as the working directory, relative paths in
+ * subproject scripts and tasks resolve against their own folder.
+ */
+pub fn with_current_dir<T>(
+	directory: &Path,
+	f: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+	let previous = env::current_dir()?;
+	env::set_current_dir(directory)?;
+	let result = f();
+	env::set_current_dir(previous)?;
+
+	result
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
 pub fn download_vswhere<P: AsRef<Path>>(path: &P) -> anyhow::Result<()> {
 	let response = reqwest::blocking::get("https://github.com/microsoft/vswhere/releases/latest/download/vswhere.exe")?;
 	if response.status().is_success() {
@@ -112,79 +140,3 @@ pub fn download_vswhere<P: AsRef<Path>>(path: &P) -> anyhow::Result<()> {
 		Err(anyhow!(response.status()))
 	}
 }
-
-pub fn args_to_map(args: Vec<String>) -> HashMap<String, Option<String>> {
-	let mut output: HashMap<String, Option<String>> = HashMap::new();
-
-	for arg in args {
-		let split_arg = arg
-			.split("=")
-			.map(|val| val.to_string())
-			.collect::<Vec<String>>();
-
-		if split_arg.len() == 2 {
-			output.insert(split_arg[0].clone(), Some(split_arg[1].clone()));
-		} else if split_arg.len() > 1 {
-			output.insert(
-				split_arg[0].clone(),
-				Some(
-					split_arg[1..]
-						.iter()
-						.map(|val| val.clone() + "=")
-						.collect::<String>(),
-				),
-			);
-		} else {
-			output.insert(split_arg[0].clone(), None);
-		}
-	}
-
-	output
-}
-
-pub fn get_gcc_includes(cmd: String) -> anyhow::Result<Vec<String>> {
-	let result = Command::new(&cmd).args(["-E", "-v", "-"]).output();
-	if result.is_ok() {
-		let mut return_vec: Vec<String> = Vec::new();
-		let mut output =
-			String::from_utf8_lossy(result.ok().unwrap().stderr.as_slice())
-				.to_string();
-		output = output
-			.split("#include <...> search starts here:")
-			.collect::<Vec<&str>>()[1]
-			.to_string();
-		output = output.split("End of search list.").collect::<Vec<&str>>()[0]
-			.to_string();
-		for line_r in output.lines() {
-			let mut line = line_r.to_string();
-			if line.is_empty() {
-				continue;
-			}
-
-			while line.starts_with(' ') {
-				line.replace_range(0..1, "");
-			}
-
-			line = line.replace(" /", "/");
-
-			if line.ends_with(" (framework directory)") {
-				// MacOS specific
-				return_vec.push(
-					dunce::canonicalize(
-						line.replace(" (framework directory)", ""),
-					)?
-					.to_str()
-					.unwrap()
-					.to_string(),
-				);
-			} else {
-				return_vec.push(
-					dunce::canonicalize(line)?.to_str().unwrap().to_string(),
-				);
-			}
-		}
-		Ok(return_vec)
-	} else {
-		Ok(Vec::default())
-	}
-}
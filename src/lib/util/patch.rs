@@ -0,0 +1,230 @@
+use crate::lib::util::error::NuMakeError::PatchFailed;
+use anyhow::anyhow;
+use diffy::Patch;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const DEV_NULL: &str = "/dev/null";
+
+/*
+ * One file's section of a unified diff.
+ */
+struct FilePatch {
+	original: String,
+	modified: String,
+	text: String,
+}
+
+/*
+ * Applies a unified diff, as produced by `diff -u` or `git diff`, to a
+ * directory. Patches that are already applied are skipped, so applying
+ * the same file twice is harmless.
+ */
+pub struct Patcher<'a> {
+	pub patch: &'a Path,
+	pub root: &'a Path,
+}
+
+impl Patcher<'_> {
+	fn failed(
+		&self,
+		file: &str,
+		reason: impl ToString,
+	) -> anyhow::Error {
+		anyhow!(PatchFailed {
+			patch: self.patch.display().to_string(),
+			file: file.to_string(),
+			reason: reason.to_string(),
+		})
+	}
+
+	/*
+	 * Returns how many files were changed.
+	 */
+	pub fn apply(&self) -> anyhow::Result<usize> {
+		let text = fs::read_to_string(self.patch)
+			.map_err(|err| self.failed("", err))?;
+
+		let files = Self::split(&text);
+		if files.is_empty() {
+			return Err(self.failed("", "no file sections found"));
+		}
+
+		let mut changed = 0;
+		for file in files {
+			if self.apply_file(&file)? {
+				changed += 1;
+			}
+		}
+
+		Ok(changed)
+	}
+
+	fn apply_file(
+		&self,
+		file: &FilePatch,
+	) -> anyhow::Result<bool> {
+		let name = match file.modified == DEV_NULL {
+			true => &file.original,
+			false => &file.modified,
+		};
+		let relative = self.relative_path(file, name)?;
+		let name = relative.to_string_lossy().to_string();
+		let path = self.root.join(&relative);
+
+		let patch = Patch::from_bytes(file.text.as_bytes())
+			.map_err(|err| self.failed(&name, err))?;
+		// Would count as both created and deleted below.
+		if patch.hunks().is_empty() {
+			return Err(self.failed(&name, "no hunks found"));
+		}
+
+		// diff -N marks added and removed files with empty ranges instead of /dev/null.
+		let created = file.original == DEV_NULL
+			|| patch.hunks().iter().all(|hunk| hunk.old_range().is_empty());
+		let deleted = file.modified == DEV_NULL
+			|| patch.hunks().iter().all(|hunk| hunk.new_range().is_empty());
+
+		let base = match created && !path.exists() {
+			true => Vec::new(),
+			false => fs::read(&path).map_err(|err| self.failed(&name, err))?,
+		};
+
+		match diffy::apply_bytes(&base, &patch) {
+			Ok(result) => {
+				if deleted && result.is_empty() {
+					fs::remove_file(&path)?;
+				} else {
+					if let Some(parent) = path.parent() {
+						fs::create_dir_all(parent)?;
+					}
+					fs::write(&path, result)?;
+				}
+				Ok(true)
+			}
+
+			// Applying in reverse works when the change is already there.
+			Err(err) => match diffy::apply_bytes(&base, &patch.reverse()) {
+				Ok(_) => Ok(false),
+				Err(_) => Err(self.failed(&name, err)),
+			},
+		}
+	}
+
+	/*
+	 * git's a/ and b/ prefixes are dropped, like patch -p1 would. The
+	 * result has to stay inside the tree being patched.
+	 */
+	fn relative_path(
+		&self,
+		file: &FilePatch,
+		name: &str,
+	) -> anyhow::Result<PathBuf> {
+		let prefixed = |path: &str, prefix: &str| {
+			path == DEV_NULL || path.starts_with(prefix)
+		};
+		let name = match prefixed(&file.original, "a/") && prefixed(&file.modified, "b/") {
+			true => &name[2..],
+			false => name,
+		};
+
+		let mut path = PathBuf::new();
+		for component in Path::new(name).components() {
+			match component {
+				Component::Normal(part) => path.push(part),
+				Component::CurDir => {}
+				_ => return Err(self.failed(name, "path leaves the patched directory")),
+			}
+		}
+
+		Ok(path)
+	}
+
+	/*
+	 * A new file section starts at a "--- " line directly followed by
+	 * "+++ ". Hunk line counts are tracked so removed lines that happen
+	 * to start with "-- " aren't mistaken for one.
+	 */
+	fn split(text: &str) -> Vec<FilePatch> {
+		let lines: Vec<&str> = text.split_inclusive('\n').collect();
+		let mut files: Vec<FilePatch> = Vec::new();
+		let (mut old_left, mut new_left) = (0usize, 0usize);
+
+		let file_name = |line: &str, prefix: &str| {
+			let name = line.trim_start_matches(prefix).trim_end();
+			// Strip the timestamp diff -u appends after a tab.
+			name.split('\t').next().unwrap_or(name).trim_matches('"').to_string()
+		};
+
+		let mut i = 0;
+		while i < lines.len() {
+			let line = lines[i];
+
+			if old_left == 0
+				&& new_left == 0
+				&& line.starts_with("--- ")
+				&& lines.get(i + 1).is_some_and(|next| next.starts_with("+++ "))
+			{
+				files.push(FilePatch {
+					original: file_name(line, "--- "),
+					modified: file_name(lines[i + 1], "+++ "),
+					text: line.to_string() + lines[i + 1],
+				});
+				i += 2;
+				continue;
+			}
+
+			let Some(file) = files.last_mut() else {
+				i += 1;
+				continue;
+			};
+
+			if old_left == 0 && new_left == 0 {
+				if let Some((old, new)) = Self::hunk_header(line) {
+					(old_left, new_left) = (old, new);
+					file.text.push_str(line);
+				}
+			} else {
+				match line.chars().next() {
+					Some('-') => old_left = old_left.saturating_sub(1),
+					Some('+') => new_left = new_left.saturating_sub(1),
+					Some('\\') => {}
+					_ => {
+						old_left = old_left.saturating_sub(1);
+						new_left = new_left.saturating_sub(1);
+					}
+				}
+				file.text.push_str(line);
+			}
+
+			// "\ No newline at end of file" belongs to the hunk that just ended.
+			if old_left == 0 && new_left == 0 {
+				if let Some(next) = lines.get(i + 1).filter(|next| next.starts_with('\\')) {
+					file.text.push_str(next);
+					i += 1;
+				}
+			}
+
+			i += 1;
+		}
+
+		files
+	}
+
+	/*
+	 * "@@ -1,7 +1,8 @@" -> (7, 8), a missing count means 1.
+	 */
+	fn hunk_header(line: &str) -> Option<(usize, usize)> {
+		let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+		let (old, new) = ranges.split_once(' ')?;
+
+		let count = |range: &str| -> Option<usize> {
+			match range.split_once(',') {
+				Some((_, count)) => count.parse().ok(),
+				None => Some(1),
+			}
+		};
+
+		Some((count(old.strip_prefix('-')?)?, count(new.strip_prefix('+')?)?))
+	}
+}
@@ -0,0 +1,147 @@
+use crate::lib::data::config::Config;
+use crate::lib::util::error::NuMakeError::{PathNotWritable, PathOutsideWorkingDirectory};
+use anyhow::anyhow;
+use mlua::Lua;
+use std::path::{Component, Path, PathBuf};
+
+/*
+ * Where scripts may read and write. Reads are allowed inside the project,
+ * numake_build, the download cache and the roots listed under [paths] in
+ * numake.toml, writes only inside numake_build. Paths are checked with
+ * symlinks resolved, a link can't lead anywhere the policy doesn't allow.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PathPolicy {
+	read: Vec<PathBuf>,
+	write: Vec<PathBuf>,
+}
+
+impl PathPolicy {
+	pub fn new(
+		project_directory: &Path,
+		numake_directory: &Path,
+		cache_directory: &Path,
+		config: &Config,
+	) -> Self {
+		let read = [project_directory, numake_directory, cache_directory]
+			.into_iter()
+			.map(Path::to_path_buf)
+			.chain(config.paths.read.iter().map(|root| project_directory.join(root)))
+			.map(|root| Self::resolve(&root))
+			.collect();
+
+		PathPolicy {
+			read,
+			write: vec![Self::resolve(numake_directory)],
+		}
+	}
+
+	/*
+	 * The policy for a subproject with a numake.toml of its own, its
+	 * [paths] are readable along with everything the parent's policy
+	 * allows.
+	 */
+	pub fn with_config(
+		&self,
+		directory: &Path,
+		config: &Config,
+	) -> Self {
+		let mut read = self.read.clone();
+		read.extend(
+			config
+				.paths
+				.read
+				.iter()
+				.map(|root| Self::resolve(&directory.join(root))),
+		);
+
+		PathPolicy {
+			read,
+			write: self.write.clone(),
+		}
+	}
+
+	/*
+	 * The absolute path with symlinks resolved as far as it exists. The
+	 * part that doesn't exist yet can't be a link, `..` in it is applied
+	 * as written.
+	 */
+	pub fn resolve(path: &Path) -> PathBuf {
+		let path = std::path::absolute(path).unwrap_or(path.to_path_buf());
+		let mut existing = path.as_path();
+		let mut rest = Vec::new();
+
+		loop {
+			if let Ok(mut resolved) = dunce::canonicalize(existing) {
+				for component in rest.iter().rev() {
+					match component {
+						Component::ParentDir => {
+							resolved.pop();
+						}
+						Component::Normal(part) => resolved.push(part),
+						_ => {}
+					}
+				}
+				return resolved;
+			}
+
+			match (existing.parent(), existing.components().next_back()) {
+				(Some(parent), Some(component)) => {
+					rest.push(component);
+					existing = parent;
+				}
+				_ => return path,
+			}
+		}
+	}
+
+	fn check(
+		roots: &[PathBuf],
+		path: &Path,
+	) -> Option<PathBuf> {
+		let resolved = Self::resolve(path);
+		roots
+			.iter()
+			.any(|root| resolved.starts_with(root))
+			.then_some(resolved)
+	}
+
+	/*
+	 * The resolved path, or PathOutsideWorkingDirectory.
+	 */
+	pub fn readable(
+		&self,
+		path: impl AsRef<Path>,
+	) -> anyhow::Result<PathBuf> {
+		let path = path.as_ref();
+		Self::check(&self.read, path)
+			.ok_or_else(|| anyhow!(PathOutsideWorkingDirectory(path.display().to_string())))
+	}
+
+	/*
+	 * The resolved path, or PathNotWritable.
+	 */
+	pub fn writable(
+		&self,
+		path: impl AsRef<Path>,
+	) -> anyhow::Result<PathBuf> {
+		let path = path.as_ref();
+		Self::check(&self.write, path)
+			.ok_or_else(|| anyhow!(PathNotWritable(path.display().to_string())))
+	}
+
+	/*
+	 * For Lua methods that don't know the environment, like Project's.
+	 * Runtime registers the policy with the Lua state.
+	 */
+	pub fn readable_in(
+		lua: &Lua,
+		path: impl AsRef<Path>,
+	) -> mlua::Result<PathBuf> {
+		let policy = lua
+			.app_data_ref::<PathPolicy>()
+			.ok_or_else(|| mlua::Error::RuntimeError("No path policy registered!".to_string()))?;
+
+		policy.readable(path).map_err(mlua::Error::external)
+	}
+}
@@ -0,0 +1,91 @@
+use crate::lib::util::cache::Cache;
+use mlua::Compiler;
+
+/*
+ * Compiled Luau bytecode, keyed by a hash of the source together with
+ * everything else that shapes the output: the numake version (and with
+ * it the bundled Luau) and the compiler options. Editing a script, or
+ * upgrading numake, can never run stale bytecode.
+ *
+ * Debug info is always compiled in, errors need the script's line numbers.
+ */
+#[derive(Clone)]
+pub struct ScriptCache {
+	cache: Cache,
+	compiler: Compiler,
+	enabled: bool,
+}
+
+impl ScriptCache {
+	pub fn new(
+		cache: Cache,
+		enabled: bool,
+	) -> Self {
+		ScriptCache {
+			cache,
+			compiler: Compiler::new()
+				.set_optimization_level(2)
+				.set_debug_level(2)
+				.set_coverage_level(2),
+			enabled,
+		}
+	}
+
+	fn key(
+		&self,
+		source: &[u8],
+	) -> String {
+		let mut input = format!(
+			"numake {}\n{:?}\n",
+			env!("CARGO_PKG_VERSION"),
+			self.compiler
+		)
+		.into_bytes();
+		input.extend_from_slice(source);
+
+		"bytecode:".to_string() + &sha256::digest(&input)
+	}
+
+	/*
+	 * `name` is the script's chunk name, syntax errors are reported with it.
+	 */
+	pub fn compile(
+		&mut self,
+		name: &str,
+		source: &[u8],
+	) -> anyhow::Result<Vec<u8>> {
+		if !self.enabled {
+			return Ok(Self::name_errors(name, self.compiler.compile(source))?);
+		}
+
+		let key = self.key(source);
+		if let Ok(bytecode) = self.cache.read_file(&key) {
+			return Ok(bytecode);
+		}
+
+		let bytecode = Self::name_errors(name, self.compiler.compile(source))?;
+		self.cache.write_file(&key, &bytecode)?;
+
+		Ok(bytecode)
+	}
+
+	/*
+	 * The compiler only knows the line, ":3: Expected ..." becomes
+	 * "project.lua:3: Expected ...".
+	 */
+	fn name_errors(
+		name: &str,
+		result: mlua::Result<Vec<u8>>,
+	) -> mlua::Result<Vec<u8>> {
+		result.map_err(|err| match err {
+			mlua::Error::SyntaxError {
+				message,
+				incomplete_input,
+			} => mlua::Error::SyntaxError {
+				message: format!("{}:{}", name, message.trim_start_matches(':')),
+				incomplete_input,
+			},
+			err => err,
+		})
+	}
+}
@@ -0,0 +1,181 @@
+use crate::lib::util::error::NuMakeError::ScriptMemoryLimit;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+
+const TRACEBACK: &str = "stack traceback:";
+
+/*
+ * A script or task that failed, pointing at the line of the script it
+ * failed on:
+ *
+ *   project.lua:12: attempt to call a nil value (in task build)
+ *
+ *      11 | tasks:create("build", function()
+ *    > 12 |     compile()
+ *      13 | end)
+ *
+ *   stack traceback:
+ *   	...
+ */
+#[derive(Debug)]
+pub struct ScriptError {
+	message: String,
+	task: Option<String>,
+	location: Option<(String, usize)>,
+	excerpt: Vec<(usize, String)>,
+	traceback: Option<String>,
+}
+
+impl ScriptError {
+	/*
+	 * Script names are relative to the root project directory, which is
+	 * the working directory whenever this is created.
+	 */
+	pub fn new(
+		error: mlua::Error,
+		task: Option<&str>,
+	) -> Self {
+		let mut tracebacks = Vec::new();
+		let mut error = &error;
+
+		// Every Rust function in between adds the frames up to its own call,
+		// the innermost cause has the message.
+		loop {
+			match error {
+				mlua::Error::CallbackError { traceback, cause } => {
+					tracebacks.push(traceback.trim_start_matches(TRACEBACK).to_string());
+					error = cause;
+				}
+				mlua::Error::WithContext { cause, .. } => error = cause,
+				_ => break,
+			}
+		}
+
+		let text = match error {
+			mlua::Error::RuntimeError(message) => message.clone(),
+			mlua::Error::SyntaxError { message, .. } => message.clone(),
+			mlua::Error::MemoryError(_) => ScriptMemoryLimit.to_string(),
+			error => error.to_string(),
+		};
+		let message = match text.split_once(TRACEBACK) {
+			Some((message, rest)) => {
+				tracebacks.push(rest.to_string());
+				message.trim_end().to_string()
+			}
+			None => text,
+		};
+
+		// Innermost frames first, like a single traceback would list them. A
+		// Lua error's own traceback often already ends with the outer frames.
+		let traceback = tracebacks
+			.iter()
+			.rev()
+			.map(|traceback| traceback.trim_matches('\n'))
+			.fold(String::new(), |combined, traceback| {
+				match combined.trim_end().ends_with(traceback.trim()) {
+					true => combined,
+					false if combined.is_empty() => traceback.to_string(),
+					false => combined + "\n" + traceback,
+				}
+			});
+		let traceback = Some(traceback).filter(|traceback| !traceback.trim().is_empty());
+
+		// Errors raised by Rust functions don't say where they were called from.
+		let location = Self::location(&message).or_else(|| {
+			traceback
+				.as_deref()
+				.and_then(|traceback| traceback.lines().find_map(|line| Self::location(line.trim())))
+		});
+		let message = match &location {
+			Some(_) if Self::location(&message).is_some() => {
+				message.splitn(3, ':').nth(2).unwrap_or_default().trim().to_string()
+			}
+			_ => message,
+		};
+
+		let excerpt = location
+			.as_ref()
+			.map(|(file, line)| Self::excerpt(file, *line))
+			.unwrap_or_default();
+
+		ScriptError {
+			message,
+			task: task.map(String::from),
+			location,
+			excerpt,
+			traceback,
+		}
+	}
+
+	/*
+	 * "libs/net/project.lua:12: ..." -> ("libs/net/project.lua", 12). C
+	 * functions and unnamed chunks have no location.
+	 */
+	fn location(text: &str) -> Option<(String, usize)> {
+		let mut parts = text.splitn(3, ':');
+		let file = parts.next()?;
+		let line = parts.next()?.parse().ok()?;
+		parts.next()?;
+
+		match file.is_empty() || file.starts_with('[') {
+			true => None,
+			false => Some((file.to_string(), line)),
+		}
+	}
+
+	fn excerpt(
+		file: &str,
+		line: usize,
+	) -> Vec<(usize, String)> {
+		let Ok(source) = fs::read_to_string(file) else {
+			return Vec::new();
+		};
+
+		source
+			.lines()
+			.enumerate()
+			.map(|(index, text)| (index + 1, text.to_string()))
+			.skip(line.saturating_sub(3))
+			.take(line.min(3) + 2)
+			.collect()
+	}
+}
+
+impl Display for ScriptError {
+	fn fmt(
+		&self,
+		f: &mut Formatter<'_>,
+	) -> fmt::Result {
+		if let Some((file, line)) = &self.location {
+			write!(f, "{}:{}: ", file, line)?;
+		}
+		write!(f, "{}", self.message)?;
+		if let Some(task) = &self.task {
+			write!(f, " (in task {})", task)?;
+		}
+
+		if let Some((_, line)) = &self.location {
+			let width = self
+				.excerpt
+				.last()
+				.map(|(number, _)| number.to_string().len())
+				.unwrap_or(0);
+
+			if !self.excerpt.is_empty() {
+				writeln!(f)?;
+			}
+			for (number, text) in &self.excerpt {
+				let marker = if number == line { ">" } else { " " };
+				write!(f, "\n  {} {:>width$} | {}", marker, number, text, width = width)?;
+			}
+		}
+
+		if let Some(traceback) = &self.traceback {
+			write!(f, "\n\n{}\n{}", TRACEBACK, traceback)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl std::error::Error for ScriptError {}
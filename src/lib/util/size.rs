@@ -0,0 +1,82 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/*
+ * A byte count written either as a plain number or with a binary suffix,
+ * e.g. 512, "64K", "5G" or "1.5GiB".
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Size(pub u64);
+
+const UNITS: [(&str, u64); 5] = [
+	("K", 1 << 10),
+	("M", 1 << 20),
+	("G", 1 << 30),
+	("T", 1 << 40),
+	("B", 1),
+];
+
+impl FromStr for Size {
+	type Err = String;
+
+	fn from_str(str: &str) -> Result<Self, Self::Err> {
+		let str = str.trim();
+		let upper = str.to_uppercase();
+		let number = upper
+			.trim_end_matches("IB")
+			.trim_end_matches('B');
+
+		let (number, multiplier) = match UNITS
+			.iter()
+			.find(|(suffix, _)| number.ends_with(suffix))
+		{
+			Some((suffix, multiplier)) => {
+				(number.trim_end_matches(suffix), *multiplier)
+			}
+			None => (number, 1),
+		};
+
+		match number.trim().parse::<f64>() {
+			Ok(number) if number >= 0.0 => {
+				Ok(Size((number * multiplier as f64) as u64))
+			}
+			_ => Err(format!("Invalid size: {str}")),
+		}
+	}
+}
+
+impl Display for Size {
+	fn fmt(
+		&self,
+		f: &mut Formatter<'_>,
+	) -> std::fmt::Result {
+		match UNITS
+			.iter()
+			.take(4)
+			.rev()
+			.find(|(_, multiplier)| self.0 >= *multiplier)
+		{
+			Some((suffix, multiplier)) => {
+				write!(f, "{:.1}{}iB", self.0 as f64 / *multiplier as f64, suffix)
+			}
+			None => write!(f, "{}B", self.0),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Size {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Raw {
+			Bytes(u64),
+			Text(String),
+		}
+
+		match Raw::deserialize(deserializer)? {
+			Raw::Bytes(bytes) => Ok(Size(bytes)),
+			Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+		}
+	}
+}
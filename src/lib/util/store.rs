@@ -0,0 +1,450 @@
+use crate::lib::util::archive::ArchiveFormat;
+use crate::lib::util::hash_string;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/*
+ * How blobs are compressed on disk. The codec is part of the file name,
+ * so changing it doesn't invalidate what's already stored.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+	None,
+	#[default]
+	Zstd,
+	Bzip2,
+}
+
+impl Codec {
+	const ALL: [Codec; 3] = [Codec::None, Codec::Zstd, Codec::Bzip2];
+
+	fn extension(self) -> &'static str {
+		match self {
+			Codec::None => "raw",
+			Codec::Zstd => "zst",
+			Codec::Bzip2 => "bz2",
+		}
+	}
+
+	fn encode<R: Read>(
+		self,
+		mut reader: R,
+		file: &mut File,
+	) -> io::Result<()> {
+		match self {
+			Codec::None => io::copy(&mut reader, file).map(|_| ()),
+			Codec::Zstd => {
+				let mut encoder = zstd::stream::write::Encoder::new(file, 3)?;
+				io::copy(&mut reader, &mut encoder)?;
+				encoder.finish().map(|_| ())
+			}
+			Codec::Bzip2 => {
+				let mut encoder =
+					bzip2::write::BzEncoder::new(file, bzip2::Compression::default());
+				io::copy(&mut reader, &mut encoder)?;
+				encoder.finish().map(|_| ())
+			}
+		}
+	}
+
+	fn decode(
+		self,
+		file: File,
+	) -> io::Result<Box<dyn Read>> {
+		Ok(match self {
+			Codec::None => Box::new(BufReader::new(file)),
+			Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+			Codec::Bzip2 => Box::new(bzip2::read::BzDecoder::new(BufReader::new(file))),
+		})
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct NameFile {
+	name: String,
+	sha256: String,
+}
+
+/*
+ * A stored blob and every name that points at it.
+ */
+pub struct Blob {
+	pub sha256: String,
+	pub names: Vec<String>,
+	pub size: u64,
+	pub last_used: u64,
+	path: PathBuf,
+}
+
+/*
+ * Content-addressed blob store shared by every project on the machine,
+ * so a dependency is downloaded and compressed once no matter how many
+ * projects use it. Blobs are named by the sha256 of their contents,
+ * names (URLs, mostly) only point at a blob.
+ *
+ *   blobs/<sha256[..2]>/<sha256>.<codec>   contents
+ *   names/<hash of name>                   the name and the blob it last had
+ *   partial/<hash of name>.part            unfinished downloads
+ *   partial/<hash of name>.lock            held by whoever is downloading it
+ *
+ * Every write goes through a temporary file and a rename, projects
+ * building at the same time never see half a blob. A partial download
+ * is only written by the numake holding its lock. A blob's modification
+ * time is its last use, there's no shared index to keep consistent.
+ */
+#[derive(Clone, Default)]
+pub struct Store {
+	directory: PathBuf,
+	codec: Codec,
+}
+
+impl Store {
+	pub fn new(
+		directory: &Path,
+		codec: Codec,
+	) -> anyhow::Result<Self> {
+		for subdirectory in ["blobs", "names", "partial"] {
+			fs::create_dir_all(directory.join(subdirectory))?;
+		}
+
+		Ok(Store {
+			directory: directory.to_path_buf(),
+			codec,
+		})
+	}
+
+	/*
+	 * NUMAKE_CACHE_DIR, otherwise the platform's user cache directory.
+	 * None when there is no home to put it in.
+	 */
+	pub fn default_directory() -> Option<PathBuf> {
+		if let Some(path) = env::var_os("NUMAKE_CACHE_DIR") {
+			return Some(PathBuf::from(path));
+		}
+
+		let base = if cfg!(windows) {
+			env::var_os("LOCALAPPDATA").map(PathBuf::from)
+		} else if cfg!(target_os = "macos") {
+			env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+		} else {
+			env::var_os("XDG_CACHE_HOME")
+				.filter(|path| Path::new(path).is_absolute())
+				.map(PathBuf::from)
+				.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		};
+
+		base.map(|base| base.join("numake"))
+	}
+
+	fn blob_path(
+		&self,
+		sha256: &str,
+		codec: Codec,
+	) -> PathBuf {
+		self.directory
+			.join("blobs")
+			.join(&sha256[..2.min(sha256.len())])
+			.join(format!("{}.{}", sha256, codec.extension()))
+	}
+
+	/*
+	 * The stored file and the codec it was written with.
+	 */
+	fn find(
+		&self,
+		sha256: &str,
+	) -> Option<(PathBuf, Codec)> {
+		Codec::ALL
+			.into_iter()
+			.map(|codec| (self.blob_path(sha256, codec), codec))
+			.find(|(path, _)| path.exists())
+	}
+
+	fn name_path(
+		&self,
+		name: &str,
+	) -> PathBuf {
+		self.directory.join("names").join(hash_string(name))
+	}
+
+	fn write_atomic(
+		&self,
+		path: &Path,
+		write: impl FnOnce(&mut File) -> io::Result<()>,
+	) -> anyhow::Result<()> {
+		let parent = path.parent().unwrap_or(&self.directory);
+		fs::create_dir_all(parent)?;
+
+		let mut file = NamedTempFile::new_in(parent)?;
+		write(file.as_file_mut())?;
+		file.persist(path)?;
+
+		Ok(())
+	}
+
+	fn read_name(path: &Path) -> Option<NameFile> {
+		toml::from_str(&fs::read_to_string(path).ok()?).ok()
+	}
+
+	fn touch(path: &Path) {
+		if let Ok(file) = File::options().write(true).open(path) {
+			let _ = file.set_modified(SystemTime::now());
+		}
+	}
+
+	/*
+	 * Archives and other compressed data are stored as they are, another
+	 * round of compression only costs time.
+	 */
+	fn codec_for(
+		&self,
+		head: &[u8],
+	) -> Codec {
+		match ArchiveFormat::is_compressed(head) {
+			true => Codec::None,
+			false => self.codec,
+		}
+	}
+
+	pub fn directory(&self) -> &Path {
+		&self.directory
+	}
+
+	pub fn contains(
+		&self,
+		sha256: &str,
+	) -> bool {
+		self.find(sha256).is_some()
+	}
+
+	/*
+	 * Stores the data and returns its sha256. Data that's already stored
+	 * isn't written again.
+	 */
+	pub fn put(
+		&self,
+		data: &[u8],
+	) -> anyhow::Result<String> {
+		let sha256 = sha256::digest(data);
+		match self.find(&sha256) {
+			Some((path, _)) => Self::touch(&path),
+			None => {
+				let codec = self.codec_for(data);
+				self.write_atomic(&self.blob_path(&sha256, codec), |file| {
+					codec.encode(data, file)
+				})?
+			}
+		}
+
+		Ok(sha256)
+	}
+
+	/*
+	 * Moves a file into the store and returns its sha256, without ever
+	 * holding all of it in memory.
+	 */
+	pub fn put_file(
+		&self,
+		source: &Path,
+	) -> anyhow::Result<String> {
+		let sha256 = sha256::try_digest(source)?;
+		if let Some((path, _)) = self.find(&sha256) {
+			Self::touch(&path);
+			fs::remove_file(source)?;
+			return Ok(sha256);
+		}
+
+		let mut head = Vec::new();
+		File::open(source)?.take(16).read_to_end(&mut head)?;
+		let codec = self.codec_for(&head);
+		let path = self.blob_path(&sha256, codec);
+
+		// Partial downloads live in the store, so nothing has to be copied.
+		if codec == Codec::None {
+			fs::create_dir_all(path.parent().unwrap_or(&self.directory))?;
+			if fs::rename(source, &path).is_ok() {
+				return Ok(sha256);
+			}
+		}
+
+		self.write_atomic(&path, |file| codec.encode(File::open(source)?, file))?;
+		fs::remove_file(source)?;
+
+		Ok(sha256)
+	}
+
+	/*
+	 * Streams a blob's contents.
+	 */
+	pub fn open(
+		&self,
+		sha256: &str,
+	) -> anyhow::Result<Box<dyn Read>> {
+		let (path, codec) = self
+			.find(sha256)
+			.ok_or_else(|| anyhow::anyhow!("Blob {} isn't stored!", sha256))?;
+		Self::touch(&path);
+
+		Ok(codec.decode(File::open(path)?)?)
+	}
+
+	/*
+	 * The blob's file when it's stored uncompressed and can be read, or
+	 * seeked in, directly.
+	 */
+	pub fn raw_path(
+		&self,
+		sha256: &str,
+	) -> Option<PathBuf> {
+		let (path, codec) = self.find(sha256)?;
+		if codec != Codec::None {
+			return None;
+		}
+
+		Self::touch(&path);
+		Some(path)
+	}
+
+	pub fn get(
+		&self,
+		sha256: &str,
+	) -> anyhow::Result<Vec<u8>> {
+		let mut buf = Vec::new();
+		self.open(sha256)?.read_to_end(&mut buf)?;
+
+		Ok(buf)
+	}
+
+	pub fn link(
+		&self,
+		name: &str,
+		sha256: &str,
+	) -> anyhow::Result<()> {
+		let contents = toml::to_string(&NameFile {
+			name: name.to_string(),
+			sha256: sha256.to_string(),
+		})?;
+
+		self.write_atomic(&self.name_path(name), |file| {
+			file.write_all(contents.as_bytes())
+		})
+	}
+
+	/*
+	 * The blob a name was last stored as, if it's still there.
+	 */
+	pub fn lookup(
+		&self,
+		name: &str,
+	) -> Option<String> {
+		Self::read_name(&self.name_path(name))
+			.map(|file| file.sha256)
+			.filter(|sha256| self.contains(sha256))
+	}
+
+	/*
+	 * Every stored blob. Names whose blob is gone are cleaned up on the way.
+	 */
+	pub fn blobs(&self) -> anyhow::Result<Vec<Blob>> {
+		let mut names: BTreeMap<String, Vec<String>> = BTreeMap::new();
+		for entry in fs::read_dir(self.directory.join("names"))? {
+			let path = entry?.path();
+			match Self::read_name(&path).filter(|file| self.contains(&file.sha256)) {
+				Some(file) => names.entry(file.sha256).or_default().push(file.name),
+				None => fs::remove_file(&path)?,
+			}
+		}
+
+		let mut blobs = Vec::new();
+		for prefix in fs::read_dir(self.directory.join("blobs"))? {
+			for entry in fs::read_dir(prefix?.path())? {
+				let path = entry?.path();
+				let sha256 = path
+					.file_stem()
+					.map(|stem| stem.to_string_lossy().to_string())
+					.unwrap_or_default();
+				// Skips temporary files of writes in progress.
+				if sha256.len() != 64 {
+					continue;
+				}
+
+				let meta = path.metadata()?;
+				blobs.push(Blob {
+					names: names.remove(&sha256).unwrap_or_default(),
+					sha256,
+					size: meta.len(),
+					last_used: meta
+						.modified()?
+						.duration_since(UNIX_EPOCH)
+						.map(|time| time.as_secs())
+						.unwrap_or(0),
+					path,
+				});
+			}
+		}
+
+		Ok(blobs)
+	}
+
+	/*
+	 * Removes a blob along with the names pointing at it.
+	 */
+	pub fn remove(
+		&self,
+		blob: &Blob,
+	) -> anyhow::Result<()> {
+		for name in &blob.names {
+			let path = self.name_path(name);
+			if path.exists() {
+				fs::remove_file(path)?;
+			}
+		}
+
+		fs::remove_file(&blob.path)?;
+		Ok(())
+	}
+
+	/*
+	 * Blocks until no other numake downloads the name, `on_wait` is called
+	 * first if one does. The lock is released when the file is dropped,
+	 * or when the process holding it dies.
+	 */
+	pub fn lock_partial(
+		&self,
+		name: &str,
+		on_wait: impl FnOnce(),
+	) -> anyhow::Result<File> {
+		let file = OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(self.directory.join("partial").join(hash_string(name) + ".lock"))?;
+
+		match file.try_lock() {
+			Ok(()) => {}
+			Err(TryLockError::WouldBlock) => {
+				on_wait();
+				file.lock()?;
+			}
+			Err(TryLockError::Error(err)) => return Err(err.into()),
+		}
+
+		Ok(file)
+	}
+
+	pub fn partial_path(
+		&self,
+		name: &str,
+	) -> PathBuf {
+		self.directory
+			.join("partial")
+			.join(hash_string(name) + ".part")
+	}
+}
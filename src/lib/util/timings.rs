@@ -0,0 +1,276 @@
+use crate::lib::ui::format::info::Info;
+use crate::lib::ui::format::ok;
+use crate::lib::ui::UI;
+use serde_json::json;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+	static THREAD: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/*
+ * Categories that wrap other spans. They're left out of the
+ * slowest steps list, they would always be on top of it.
+ */
+const CONTAINER_CATEGORIES: [&str; 2] = ["task", "script"];
+
+#[derive(Clone, Debug)]
+struct Span {
+	category: String,
+	name: String,
+	thread: u64,
+	start_us: u64,
+	duration_us: u64,
+}
+
+/*
+ * Records how long every build step takes when running with --timings.
+ * When disabled, spans are never stored so this costs next to nothing.
+ */
+#[derive(Clone)]
+pub struct Timings {
+	enabled: bool,
+	origin: Instant,
+	spans: Arc<Mutex<Vec<Span>>>,
+}
+
+pub struct SpanGuard {
+	timings: Timings,
+	category: String,
+	name: String,
+	start: Instant,
+}
+
+impl Drop for SpanGuard {
+	fn drop(&mut self) {
+		self.timings.record(
+			std::mem::take(&mut self.category),
+			std::mem::take(&mut self.name),
+			self.start,
+		);
+	}
+}
+
+impl Timings {
+	pub fn new(enabled: bool) -> Self {
+		Timings {
+			enabled,
+			origin: Instant::now(),
+			spans: Arc::new(Mutex::new(Vec::new())),
+		}
+	}
+
+	/*
+	 * The span ends when the returned guard is dropped.
+	 */
+	pub fn span(
+		&self,
+		category: &str,
+		name: impl Into<String>,
+	) -> SpanGuard {
+		SpanGuard {
+			timings: self.clone(),
+			category: category.to_string(),
+			name: name.into(),
+			start: Instant::now(),
+		}
+	}
+
+	fn record(
+		&self,
+		category: String,
+		name: String,
+		start: Instant,
+	) {
+		if !self.enabled {
+			return;
+		}
+
+		let thread = THREAD.with(|thread| match thread.get() {
+			Some(id) => id,
+			None => {
+				let id = NEXT_THREAD.fetch_add(1, Ordering::SeqCst);
+				thread.set(Some(id));
+				id
+			}
+		});
+
+		(*self.spans.lock().unwrap()).push(Span {
+			category,
+			name,
+			thread,
+			start_us: start.duration_since(self.origin).as_micros() as u64,
+			duration_us: start.elapsed().as_micros() as u64,
+		});
+	}
+
+	pub fn report(
+		&self,
+		ui: &UI,
+		count: usize,
+		out_dir: &Path,
+	) -> anyhow::Result<()> {
+		if !self.enabled {
+			return Ok(());
+		}
+
+		let spans = (*self.spans.lock().unwrap()).clone();
+
+		let mut steps: Vec<&Span> = spans
+			.iter()
+			.filter(|span| {
+				!CONTAINER_CATEGORIES.contains(&span.category.as_str())
+			})
+			.collect();
+		steps.sort_by_key(|span| Reverse(span.duration_us));
+
+		ui.println(format!("Slowest {} steps:", count), Info::default());
+		for span in steps.iter().take(count) {
+			ui.println(
+				format!(
+					"{:>10.3}s  {:<8} {}",
+					span.duration_us as f64 / 1_000_000.0,
+					span.category,
+					span.name
+				),
+				Info::default(),
+			);
+		}
+
+		if !out_dir.exists() {
+			fs::create_dir_all(out_dir)?;
+		}
+
+		let trace_path = out_dir.join("trace.json");
+		fs::write(&trace_path, Self::chrome_trace(&spans)?)?;
+
+		let html_path = out_dir.join("timings.html");
+		fs::write(&html_path, Self::html_report(&spans, &steps))?;
+
+		ui.println(
+			format!(
+				"Wrote {} and {}",
+				trace_path.display(),
+				html_path.display()
+			),
+			ok::Ok::default(),
+		);
+
+		Ok(())
+	}
+
+	/*
+	 * Chrome's trace event format, open it in chrome://tracing or Perfetto.
+	 */
+	fn chrome_trace(spans: &[Span]) -> anyhow::Result<String> {
+		let events: Vec<serde_json::Value> = spans
+			.iter()
+			.map(|span| {
+				json!({
+					"name": span.name,
+					"cat": span.category,
+					"ph": "X",
+					"ts": span.start_us,
+					"dur": span.duration_us,
+					"pid": 1,
+					"tid": span.thread,
+				})
+			})
+			.collect();
+
+		Ok(serde_json::to_string(&json!({
+			"traceEvents": events,
+			"displayTimeUnit": "ms",
+		}))?)
+	}
+
+	fn html_report(
+		spans: &[Span],
+		steps: &[&Span],
+	) -> String {
+		let total_us = spans
+			.iter()
+			.map(|span| span.start_us + span.duration_us)
+			.max()
+			.unwrap_or(0)
+			.max(1);
+
+		let mut timeline = String::new();
+		let mut sorted: Vec<&Span> = spans.iter().collect();
+		sorted.sort_by_key(|span| (span.thread, span.start_us));
+		for span in sorted {
+			let _ = writeln!(
+				timeline,
+				"<div class=\"row\"><div class=\"bar {}\" style=\"margin-left:{:.3}%;width:{:.3}%\" title=\"{} ({:.3}s)\">{}</div></div>",
+				escape_html(&span.category),
+				span.start_us as f64 * 100.0 / total_us as f64,
+				(span.duration_us as f64 * 100.0 / total_us as f64).max(0.1),
+				escape_html(&span.name),
+				span.duration_us as f64 / 1_000_000.0,
+				escape_html(&span.name),
+			);
+		}
+
+		let mut table = String::new();
+		for span in steps {
+			let _ = writeln!(
+				table,
+				"<tr><td>{:.3}s</td><td>{}</td><td>{}</td></tr>",
+				span.duration_us as f64 / 1_000_000.0,
+				escape_html(&span.category),
+				escape_html(&span.name),
+			);
+		}
+
+		format!(
+			r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>numake build timings</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+.row {{ height: 18px; margin: 1px 0; }}
+.bar {{ height: 100%; overflow: hidden; white-space: nowrap; font-size: 11px; color: white; background: #888; }}
+.task, .script {{ background: #555; }}
+.compile {{ background: #2b7bb9; }}
+.resource {{ background: #8e44ad; }}
+.link {{ background: #c0392b; }}
+.assets {{ background: #27ae60; }}
+table {{ border-collapse: collapse; }}
+td {{ padding: 2px 1em 2px 0; }}
+</style>
+</head>
+<body>
+<h1>Build timings</h1>
+<p>Total: {:.3}s</p>
+<h2>Timeline</h2>
+{}
+<h2>Steps</h2>
+<table>
+{}</table>
+</body>
+</html>
+"#,
+			total_us as f64 / 1_000_000.0,
+			timeline,
+			table
+		)
+	}
+}
+
+fn escape_html(str: &str) -> String {
+	str.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}